@@ -0,0 +1,447 @@
+use crate::{merge_params, read_body, BaseClientError, BodyEncoding, Client, Decoder};
+use async_trait::async_trait;
+use hyper::http::header::{HeaderMap, HeaderName, HeaderValue, AUTHORIZATION, CONTENT_TYPE};
+use hyper::{Body, Method, Request, Response, Uri};
+use serde::{Deserialize, Serialize};
+use std::fmt::Debug;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Duration;
+use tokio::sync::Mutex;
+use tokio::time::Instant;
+
+/// AuthProvider adds credentials to each request built from an Endpoint with a client
+/// wrapped by Client::with_auth
+#[async_trait]
+pub trait AuthProvider: Debug + Send + Sync {
+    async fn authorize(&self, request: &mut Request<Body>) -> Result<(), BaseClientError>;
+
+    /// called when a request was rejected with 401 Unauthorized, returns whether the credentials
+    /// were refreshed, in which case the request is retried once; it is passed the AuthGeneration
+    /// `authorize` inserted into the extensions of the rejected request, if any
+    async fn refresh(&self, _authorized_under: Option<AuthGeneration>) -> Result<bool, BaseClientError> {
+        Ok(false)
+    }
+
+    /// names of query params carrying credentials, redacted from fixtures by RecordingClient
+    fn sensitive_params(&self) -> Vec<&str> {
+        vec![]
+    }
+}
+
+/// AuthGeneration identifies the credentials a request was authorized with, AuthProviders that
+/// refresh their credentials insert it into the request extensions so a refresh can tell whether
+/// the credentials were already refreshed since the rejected request was authorized
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct AuthGeneration(pub u64);
+
+#[derive(Clone, Debug)]
+pub struct WithAuth<C, A> {
+    pub(crate) client: C,
+    pub(crate) auth: A,
+}
+
+impl<C, A> WithAuth<C, A> {
+    pub fn into_inner(self) -> C {
+        self.client
+    }
+}
+
+#[async_trait]
+impl<C, A> Client for WithAuth<C, A>
+where
+    C: Client + Send + Sync,
+    A: AuthProvider,
+{
+    type Error = C::Error;
+
+    fn headers(&self) -> &HeaderMap {
+        self.client.headers()
+    }
+    fn timeout(&self) -> Option<Duration> {
+        self.client.timeout()
+    }
+    fn max_response_size(&self) -> Option<usize> {
+        self.client.max_response_size()
+    }
+    fn base_uri(&self) -> Option<&str> {
+        self.client.base_uri()
+    }
+    fn auth(&self) -> Option<&dyn AuthProvider> {
+        Some(&self.auth)
+    }
+    fn is_transport_error(&self, error: &Self::Error) -> bool {
+        self.client.is_transport_error(error)
+    }
+    fn applies_timeout(&self) -> bool {
+        self.client.applies_timeout()
+    }
+    async fn rest(&self, request: Request<Body>) -> Result<Response<Body>, Self::Error> {
+        self.client.rest(request).await
+    }
+}
+
+/// sends a static token in the Authorization header as `Bearer <token>`
+#[derive(Clone, Debug)]
+pub struct BearerToken(HeaderValue);
+
+impl BearerToken {
+    pub fn new(token: impl AsRef<str>) -> Result<Self, BaseClientError> {
+        Ok(Self(sensitive_header_value(format!("Bearer {}", token.as_ref()))?))
+    }
+}
+
+#[async_trait]
+impl AuthProvider for BearerToken {
+    async fn authorize(&self, request: &mut Request<Body>) -> Result<(), BaseClientError> {
+        request.headers_mut().insert(AUTHORIZATION, self.0.clone());
+        Ok(())
+    }
+}
+
+/// sends the credentials in the Authorization header as `Basic <base64(username:password)>`
+#[derive(Clone, Debug)]
+pub struct BasicAuth(HeaderValue);
+
+impl BasicAuth {
+    pub fn new(username: impl AsRef<str>, password: Option<impl AsRef<str>>) -> Result<Self, BaseClientError> {
+        Ok(Self(basic_authorization(
+            username.as_ref(),
+            password.as_ref().map(AsRef::as_ref),
+        )?))
+    }
+}
+
+#[async_trait]
+impl AuthProvider for BasicAuth {
+    async fn authorize(&self, request: &mut Request<Body>) -> Result<(), BaseClientError> {
+        request.headers_mut().insert(AUTHORIZATION, self.0.clone());
+        Ok(())
+    }
+}
+
+/// sends an API key either in a header or in a query param
+#[derive(Clone, Debug)]
+pub enum ApiKey {
+    Header(HeaderName, HeaderValue),
+    Query(String, String),
+}
+
+impl ApiKey {
+    pub fn header(name: impl AsRef<str>, key: impl AsRef<str>) -> Result<Self, BaseClientError> {
+        let name = HeaderName::try_from(name.as_ref())
+            .map_err(|err| BaseClientError::Auth(format!("invalid api key header name: {err}")))?;
+        Ok(Self::Header(name, sensitive_header_value(key.as_ref())?))
+    }
+
+    pub fn query(name: impl Into<String>, key: impl Into<String>) -> Self {
+        Self::Query(name.into(), key.into())
+    }
+}
+
+#[async_trait]
+impl AuthProvider for ApiKey {
+    async fn authorize(&self, request: &mut Request<Body>) -> Result<(), BaseClientError> {
+        match self {
+            Self::Header(name, key) => {
+                request.headers_mut().insert(name, key.clone());
+            }
+            Self::Query(name, key) => {
+                *request.uri_mut() = merge_params(request.uri(), &[(name.clone(), key.clone())])?;
+            }
+        }
+        Ok(())
+    }
+
+    fn sensitive_params(&self) -> Vec<&str> {
+        match self {
+            Self::Header(..) => vec![],
+            Self::Query(name, _) => vec![name],
+        }
+    }
+}
+
+/// OAuth2ClientCredentials fetches access tokens from an OAuth2 token endpoint using the client
+/// credentials grant (RFC 6749 section 4.4) with the given client, the client id and secret are
+/// sent with HTTP Basic authentication; tokens are cached and fetched anew `refresh_before` their
+/// expiry or when a request was rejected with 401 Unauthorized, requests rejected concurrently
+/// share a single refresh
+#[derive(Debug)]
+pub struct OAuth2ClientCredentials<C> {
+    client: C,
+    token_uri: Uri,
+    credentials: HeaderValue,
+    scopes: Vec<String>,
+    refresh_before: Duration,
+    token: Mutex<Option<AccessToken>>,
+    /// number of tokens fetched, the AuthGeneration of requests authorized with the current token
+    fetched: AtomicU64,
+}
+
+#[derive(Debug)]
+struct AccessToken {
+    authorization: HeaderValue,
+    expires_at: Option<Instant>,
+}
+
+#[derive(Debug, Serialize)]
+struct TokenRequest {
+    grant_type: &'static str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    scope: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct TokenResponse {
+    access_token: String,
+    #[serde(default)]
+    token_type: Option<String>,
+    #[serde(default)]
+    expires_in: Option<u64>,
+}
+
+impl<C: Client + Send + Sync> OAuth2ClientCredentials<C> {
+    pub fn new(
+        client: C,
+        token_uri: Uri,
+        client_id: impl AsRef<str>,
+        client_secret: impl AsRef<str>,
+    ) -> Result<Self, BaseClientError> {
+        Ok(Self {
+            client,
+            token_uri,
+            credentials: basic_authorization(client_id.as_ref(), Some(client_secret.as_ref()))?,
+            scopes: vec![],
+            refresh_before: Duration::from_secs(30),
+            token: Mutex::default(),
+            fetched: AtomicU64::default(),
+        })
+    }
+
+    pub fn scopes(self, scopes: impl IntoIterator<Item = impl Into<String>>) -> Self {
+        Self {
+            scopes: scopes.into_iter().map(Into::into).collect(),
+            ..self
+        }
+    }
+
+    pub fn refresh_before(self, refresh_before: Duration) -> Self {
+        Self { refresh_before, ..self }
+    }
+
+    async fn fetch_token(&self) -> Result<AccessToken, BaseClientError> {
+        let (body, content_type) = BodyEncoding::Form.encode(&TokenRequest {
+            grant_type: "client_credentials",
+            scope: (!self.scopes.is_empty()).then(|| self.scopes.join(" ")),
+        })?;
+
+        let mut request = Request::builder()
+            .method(Method::POST)
+            .uri(self.token_uri.clone())
+            .body(Body::from(body))
+            .map_err(|err| BaseClientError::RequestBodyBuild(format!("{err}")))?;
+        request.headers_mut().insert(CONTENT_TYPE, content_type);
+        request.headers_mut().insert(AUTHORIZATION, self.credentials.clone());
+
+        let requested_at = Instant::now();
+        let response = self
+            .client
+            .rest(request)
+            .await
+            .map_err(|err| BaseClientError::Auth(format!("could not fetch access token: {err}")))?;
+        let (parts, body) = response.into_parts();
+        let body = read_body(body, self.client.max_response_size()).await?;
+        if !parts.status.is_success() {
+            return Err(BaseClientError::Auth(format!(
+                "token endpoint responded with status {}",
+                parts.status
+            )));
+        }
+
+        let token: TokenResponse = Decoder::Json.decode(&Response::from_parts(parts, body))?;
+        if let Some(token_type) = token.token_type.as_deref() {
+            if !token_type.eq_ignore_ascii_case("bearer") {
+                return Err(BaseClientError::Auth(format!("unsupported token type: {token_type}")));
+            }
+        }
+        Ok(AccessToken {
+            authorization: sensitive_header_value(format!("Bearer {}", token.access_token))?,
+            expires_at: token
+                .expires_in
+                .map(|expires_in| requested_at + Duration::from_secs(expires_in)),
+        })
+    }
+}
+
+impl AccessToken {
+    fn is_usable(&self, refresh_before: Duration) -> bool {
+        self.expires_at
+            .is_none_or(|expires_at| Instant::now() + refresh_before < expires_at)
+    }
+}
+
+#[async_trait]
+impl<C: Client + Send + Sync> AuthProvider for OAuth2ClientCredentials<C> {
+    async fn authorize(&self, request: &mut Request<Body>) -> Result<(), BaseClientError> {
+        // holding the lock while fetching makes concurrent requests wait for a single token fetch
+        let mut token = self.token.lock().await;
+        let authorization = match &*token {
+            Some(token) if token.is_usable(self.refresh_before) => token.authorization.clone(),
+            _ => {
+                tracing::debug!(token_uri = %self.token_uri, "fetching access token");
+                let fetched = self.fetch_token().await?;
+                let authorization = fetched.authorization.clone();
+                *token = Some(fetched);
+                self.fetched.fetch_add(1, Ordering::Release);
+                authorization
+            }
+        };
+        request.headers_mut().insert(AUTHORIZATION, authorization);
+        request
+            .extensions_mut()
+            .insert(AuthGeneration(self.fetched.load(Ordering::Acquire)));
+        Ok(())
+    }
+
+    async fn refresh(&self, authorized_under: Option<AuthGeneration>) -> Result<bool, BaseClientError> {
+        let mut token = self.token.lock().await;
+        let current = AuthGeneration(self.fetched.load(Ordering::Acquire));
+        if authorized_under.is_some_and(|authorized_under| authorized_under != current) {
+            // a token was fetched since the rejected request was authorized, retry with that one
+            return Ok(true);
+        }
+        tracing::debug!(token_uri = %self.token_uri, "refreshing access token");
+        *token = Some(self.fetch_token().await?);
+        self.fetched.fetch_add(1, Ordering::Release);
+        Ok(true)
+    }
+}
+
+fn basic_authorization(username: &str, password: Option<&str>) -> Result<HeaderValue, BaseClientError> {
+    let credentials = format!("{username}:{}", password.unwrap_or_default());
+    sensitive_header_value(format!(
+        "Basic {}",
+        data_encoding::BASE64.encode(credentials.as_bytes())
+    ))
+}
+
+fn sensitive_header_value(value: impl AsRef<str>) -> Result<HeaderValue, BaseClientError> {
+    let mut value = HeaderValue::from_str(value.as_ref())
+        .map_err(|err| BaseClientError::Auth(format!("invalid credentials: {err}")))?;
+    value.set_sensitive(true);
+    Ok(value)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use hyper::body::to_bytes;
+    use hyper::client::HttpConnector;
+    use hyper::service::{make_service_fn, service_fn};
+    use hyper::{Server, StatusCode};
+    use std::convert::Infallible;
+    use std::net::SocketAddr;
+    use std::sync::Arc;
+
+    /// serves tokens `token-1`, `token-2`, ... to the client credentials `client:secret`
+    async fn token_server() -> (Uri, Arc<AtomicU64>) {
+        let issued = Arc::new(AtomicU64::default());
+        let make_service = make_service_fn({
+            let issued = issued.clone();
+            move |_| {
+                let issued = issued.clone();
+                async move {
+                    Ok::<_, Infallible>(service_fn(move |request: Request<Body>| {
+                        let issued = issued.clone();
+                        async move {
+                            let authorized = request.headers().get(AUTHORIZATION)
+                                == Some(&basic_authorization("client", Some("secret")).unwrap());
+                            let body = to_bytes(request.into_body()).await.unwrap();
+                            let response = if !authorized {
+                                Response::builder().status(StatusCode::UNAUTHORIZED).body(Body::empty())
+                            } else if body != "grant_type=client_credentials&scope=read+write" {
+                                Response::builder().status(StatusCode::BAD_REQUEST).body(Body::empty())
+                            } else {
+                                let token = issued.fetch_add(1, Ordering::SeqCst) + 1;
+                                Response::builder()
+                                    .header(CONTENT_TYPE, "application/json")
+                                    .body(Body::from(format!(
+                                        r#"{{"access_token":"token-{token}","token_type":"Bearer","expires_in":3600}}"#
+                                    )))
+                            };
+                            Ok::<_, Infallible>(response.unwrap())
+                        }
+                    }))
+                }
+            }
+        });
+        let server = Server::bind(&SocketAddr::from(([127, 0, 0, 1], 0))).serve(make_service);
+        let uri = format!("http://{}/oauth/token", server.local_addr()).parse().unwrap();
+        tokio::spawn(server);
+        (uri, issued)
+    }
+
+    async fn authorized<A: AuthProvider>(auth: &A) -> Result<Request<Body>, BaseClientError> {
+        let mut request = Request::get("http://localhost/items").body(Body::empty()).unwrap();
+        auth.authorize(&mut request).await?;
+        Ok(request)
+    }
+
+    async fn authorization<A: AuthProvider>(auth: &A) -> Result<HeaderValue, BaseClientError> {
+        Ok(authorized(auth).await?.headers()[AUTHORIZATION].clone())
+    }
+
+    fn generation(request: &Request<Body>) -> Option<AuthGeneration> {
+        request.extensions().get::<AuthGeneration>().copied()
+    }
+
+    fn credentials(token_uri: Uri, client_secret: &str) -> OAuth2ClientCredentials<hyper::Client<HttpConnector>> {
+        OAuth2ClientCredentials::new(hyper::Client::new(), token_uri, "client", client_secret)
+            .unwrap()
+            .scopes(["read", "write"])
+    }
+
+    #[tokio::test]
+    async fn test_token_is_fetched_once_and_cached() {
+        let (token_uri, issued) = token_server().await;
+        let auth = credentials(token_uri, "secret");
+
+        assert_eq!(authorization(&auth).await.unwrap(), "Bearer token-1");
+        assert_eq!(authorization(&auth).await.unwrap(), "Bearer token-1");
+        assert_eq!(issued.load(Ordering::SeqCst), 1);
+    }
+
+    #[tokio::test]
+    async fn test_concurrent_refreshes_fetch_a_single_token() {
+        let (token_uri, issued) = token_server().await;
+        let auth = credentials(token_uri, "secret");
+        let rejected = generation(&authorized(&auth).await.unwrap());
+
+        let (first, second, third) =
+            tokio::join!(auth.refresh(rejected), auth.refresh(rejected), auth.refresh(rejected));
+        assert!(first.unwrap() && second.unwrap() && third.unwrap());
+        assert_eq!(issued.load(Ordering::SeqCst), 2);
+        assert_eq!(authorization(&auth).await.unwrap(), "Bearer token-2");
+
+        // the second rejected request only reaches refresh after the first one's refresh finished
+        let first = generation(&authorized(&auth).await.unwrap());
+        let second = generation(&authorized(&auth).await.unwrap());
+        assert!(auth.refresh(first).await.unwrap());
+        assert!(auth.refresh(second).await.unwrap());
+        assert_eq!(issued.load(Ordering::SeqCst), 3);
+        assert_eq!(authorization(&auth).await.unwrap(), "Bearer token-3");
+
+        // a request authorized with the current token is rejected again
+        let rejected = generation(&authorized(&auth).await.unwrap());
+        assert!(auth.refresh(rejected).await.unwrap());
+        assert_eq!(issued.load(Ordering::SeqCst), 4);
+    }
+
+    #[tokio::test]
+    async fn test_rejected_credentials_are_an_auth_error() {
+        let (token_uri, issued) = token_server().await;
+        let auth = credentials(token_uri, "wrong");
+
+        assert!(matches!(authorization(&auth).await, Err(BaseClientError::Auth(_))));
+        assert_eq!(issued.load(Ordering::SeqCst), 0);
+    }
+}
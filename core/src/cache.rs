@@ -0,0 +1,512 @@
+use crate::{read_body, Client, ClientMiddleware};
+use async_trait::async_trait;
+use hyper::http::header::{
+    HeaderMap, HeaderName, HeaderValue, AGE, AUTHORIZATION, CACHE_CONTROL, CONTENT_LENGTH, ETAG, EXPIRES,
+    IF_MODIFIED_SINCE, IF_NONE_MATCH, LAST_MODIFIED, PROXY_AUTHORIZATION, TRANSFER_ENCODING, VARY,
+};
+use hyper::{Body, Method, Request, Response, StatusCode};
+use std::collections::{BTreeMap, HashMap};
+use std::fmt::Debug;
+use std::sync::Mutex;
+use std::time::{Duration, SystemTime};
+
+/// CacheStore holds cached responses keyed by request uri
+#[async_trait]
+pub trait CacheStore: Debug + Send + Sync {
+    async fn get(&self, key: &str) -> Option<CachedResponse>;
+    async fn put(&self, key: String, response: CachedResponse);
+    async fn remove(&self, key: &str);
+}
+
+#[derive(Clone, Debug)]
+pub struct CachedResponse {
+    pub status: StatusCode,
+    pub headers: HeaderMap,
+    pub body: Vec<u8>,
+    /// the response is served without revalidation until this time
+    pub fresh_until: SystemTime,
+    /// the request header values named by the response's Vary header,
+    /// the entry is only used for requests with the same values
+    pub vary: Vec<(HeaderName, Option<HeaderValue>)>,
+}
+
+/// inserted into the extensions of responses passing through HttpCache
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum CacheStatus {
+    /// served from the cache without contacting the upstream
+    Fresh,
+    /// served from the cache after the upstream responded 304 Not Modified
+    Revalidated,
+    /// served by the upstream
+    Miss,
+}
+
+/// HttpCache is a ClientMiddleware caching GET responses according to their Cache-Control,
+/// Expires, ETag and Last-Modified headers: fresh entries are served from the store, stale entries
+/// are revalidated with If-None-Match / If-Modified-Since and a 304 Not Modified response is
+/// answered with the cached response, so Query impls only ever see the cached 2xx response;
+/// e.g. `client.with_middleware(HttpCache::new(LruCacheStore::new(1024)))`
+///
+/// Since a client usually sends requests on behalf of many users, HttpCache follows the rules of a
+/// shared cache: `private` responses are never stored and responses to requests carrying credentials
+/// in an Authorization header are only stored if marked `public`, `s-maxage` or `must-revalidate`;
+/// requests which are already conditional are passed through so that the caller sees the upstream's
+/// 304 Not Modified, which Query impls return as BaseClientError::NotModified
+#[derive(Debug)]
+pub struct HttpCache<S = LruCacheStore> {
+    store: S,
+}
+
+impl<S: CacheStore> HttpCache<S> {
+    pub fn new(store: S) -> Self {
+        Self { store }
+    }
+
+    pub fn store(&self) -> &S {
+        &self.store
+    }
+}
+
+#[async_trait]
+impl<C, S> ClientMiddleware<C> for HttpCache<S>
+where
+    C: Client + Sync,
+    S: CacheStore,
+{
+    async fn handle(&self, mut request: Request<Body>, client: &C) -> Result<Response<Body>, C::Error> {
+        let request_directives = CacheControl::from(request.headers());
+        let is_conditional =
+            request.headers().contains_key(IF_NONE_MATCH) || request.headers().contains_key(IF_MODIFIED_SINCE);
+        if request.method() != Method::GET || request_directives.no_store || is_conditional {
+            return client.rest(request).await;
+        }
+
+        let key = request.uri().to_string();
+        let cached = self
+            .store
+            .get(&key)
+            .await
+            .filter(|cached| cached.matches_vary(request.headers()));
+
+        if let Some(cached) = &cached {
+            if !request_directives.no_cache && SystemTime::now() < cached.fresh_until {
+                return Ok(cached.to_response(CacheStatus::Fresh));
+            }
+            if let Some(etag) = cached.headers.get(ETAG) {
+                request.headers_mut().insert(IF_NONE_MATCH, etag.clone());
+            }
+            if let Some(last_modified) = cached.headers.get(LAST_MODIFIED) {
+                request.headers_mut().insert(IF_MODIFIED_SINCE, last_modified.clone());
+            }
+        }
+
+        let request_headers = request.headers().clone();
+        let response = client.rest(request).await?;
+
+        // only requests revalidating a cached entry were made conditional
+        if let (StatusCode::NOT_MODIFIED, Some(mut cached)) = (response.status(), cached) {
+            for (name, value) in response.headers() {
+                if name != CONTENT_LENGTH && name != TRANSFER_ENCODING {
+                    cached.headers.insert(name, value.clone());
+                }
+            }
+            cached.fresh_until = fresh_until(&cached.headers);
+            let response = cached.to_response(CacheStatus::Revalidated);
+            self.store.put(key, cached).await;
+            return Ok(response);
+        }
+
+        let response_directives = CacheControl::from(response.headers());
+        let vary = vary(response.headers(), &request_headers);
+        let is_authorized =
+            request_headers.contains_key(AUTHORIZATION) || request_headers.contains_key(PROXY_AUTHORIZATION);
+        let cacheable = response.status() == StatusCode::OK
+            && !response_directives.no_store
+            && !response_directives.private
+            && (!is_authorized
+                || response_directives.public
+                || response_directives.s_maxage.is_some()
+                || response_directives.must_revalidate)
+            && (response_directives.max_age.is_some()
+                || response_directives.s_maxage.is_some()
+                || response.headers().contains_key(EXPIRES)
+                || response.headers().contains_key(ETAG)
+                || response.headers().contains_key(LAST_MODIFIED));
+        let (Some(vary), true) = (vary, cacheable) else {
+            let mut response = response;
+            response.extensions_mut().insert(CacheStatus::Miss);
+            return Ok(response);
+        };
+
+        let (mut parts, body) = response.into_parts();
+        let body = read_body(body, client.max_response_size()).await?;
+        let cached = CachedResponse {
+            status: parts.status,
+            fresh_until: fresh_until(&parts.headers),
+            headers: parts.headers.clone(),
+            body: body.clone(),
+            vary,
+        };
+        self.store.put(key, cached).await;
+
+        parts.extensions.insert(CacheStatus::Miss);
+        Ok(Response::from_parts(parts, Body::from(body)))
+    }
+}
+
+impl CachedResponse {
+    fn matches_vary(&self, request_headers: &HeaderMap) -> bool {
+        self.vary
+            .iter()
+            .all(|(name, value)| request_headers.get(name) == value.as_ref())
+    }
+
+    fn to_response(&self, cache_status: CacheStatus) -> Response<Body> {
+        let mut response = Response::new(Body::from(self.body.clone()));
+        *response.status_mut() = self.status;
+        *response.headers_mut() = self.headers.clone();
+        response.extensions_mut().insert(cache_status);
+        response
+    }
+}
+
+/// LruCacheStore is an in-memory CacheStore evicting the least recently used entry
+/// once it holds `capacity` entries
+#[derive(Debug)]
+pub struct LruCacheStore {
+    capacity: usize,
+    state: Mutex<LruState>,
+}
+
+#[derive(Debug, Default)]
+struct LruState {
+    tick: u64,
+    entries: HashMap<String, (CachedResponse, u64)>,
+    recency: BTreeMap<u64, String>,
+}
+
+impl LruCacheStore {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            state: Mutex::default(),
+        }
+    }
+}
+
+impl LruState {
+    fn touch(&mut self, key: &str) {
+        self.tick += 1;
+        let tick = self.tick;
+        if let Some((_, last_used)) = self.entries.get_mut(key) {
+            self.recency.remove(last_used);
+            *last_used = tick;
+            self.recency.insert(tick, key.into());
+        }
+    }
+}
+
+#[async_trait]
+impl CacheStore for LruCacheStore {
+    async fn get(&self, key: &str) -> Option<CachedResponse> {
+        let mut state = self.state.lock().unwrap();
+        state.touch(key);
+        state.entries.get(key).map(|(cached, _)| cached.clone())
+    }
+
+    async fn put(&self, key: String, response: CachedResponse) {
+        if self.capacity == 0 {
+            return;
+        }
+        let mut state = self.state.lock().unwrap();
+        state.tick += 1;
+        let tick = state.tick;
+        if let Some((_, last_used)) = state.entries.insert(key.clone(), (response, tick)) {
+            state.recency.remove(&last_used);
+        }
+        state.recency.insert(tick, key);
+        while state.entries.len() > self.capacity {
+            let Some((_, key)) = state.recency.pop_first() else {
+                break;
+            };
+            state.entries.remove(&key);
+        }
+    }
+
+    async fn remove(&self, key: &str) {
+        let mut state = self.state.lock().unwrap();
+        if let Some((_, last_used)) = state.entries.remove(key) {
+            state.recency.remove(&last_used);
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+struct CacheControl {
+    no_store: bool,
+    no_cache: bool,
+    /// stale responses must be revalidated, which HttpCache always does,
+    /// but it also allows storing responses to authorized requests
+    must_revalidate: bool,
+    private: bool,
+    public: bool,
+    max_age: Option<u64>,
+    s_maxage: Option<u64>,
+}
+
+impl From<&HeaderMap> for CacheControl {
+    fn from(headers: &HeaderMap) -> Self {
+        let mut cache_control = Self::default();
+        for directive in headers
+            .get_all(CACHE_CONTROL)
+            .iter()
+            .filter_map(|value| value.to_str().ok())
+            .flat_map(|value| value.split(','))
+        {
+            let (name, value) = match directive.split_once('=') {
+                Some((name, value)) => (name.trim(), Some(value.trim().trim_matches('"'))),
+                None => (directive.trim(), None),
+            };
+            match name.to_ascii_lowercase().as_str() {
+                "no-store" => cache_control.no_store = true,
+                "no-cache" => cache_control.no_cache = true,
+                "must-revalidate" | "proxy-revalidate" => cache_control.must_revalidate = true,
+                "private" => cache_control.private = true,
+                "public" => cache_control.public = true,
+                "max-age" => cache_control.max_age = value.and_then(|value| value.parse().ok()),
+                "s-maxage" => cache_control.s_maxage = value.and_then(|value| value.parse().ok()),
+                _ => {}
+            }
+        }
+        cache_control
+    }
+}
+
+/// computed from s-maxage or max-age (less the response's Age) or else Expires, responses
+/// without either are stale immediately and are revalidated on every request
+fn fresh_until(headers: &HeaderMap) -> SystemTime {
+    let now = SystemTime::now();
+    let cache_control = CacheControl::from(headers);
+    if cache_control.no_cache {
+        return now;
+    }
+    if let Some(max_age) = cache_control.s_maxage.or(cache_control.max_age) {
+        let age = headers
+            .get(AGE)
+            .and_then(|age| age.to_str().ok()?.parse::<u64>().ok())
+            .unwrap_or_default();
+        return now + Duration::from_secs(max_age.saturating_sub(age));
+    }
+    headers
+        .get(EXPIRES)
+        .and_then(|expires| chrono::DateTime::parse_from_rfc2822(expires.to_str().ok()?).ok())
+        .map(|expires| SystemTime::from(expires.with_timezone(&chrono::Utc)))
+        .unwrap_or(now)
+}
+
+/// None if the response varies on `*` and so can't be cached
+fn vary(response_headers: &HeaderMap, request_headers: &HeaderMap) -> Option<Vec<(HeaderName, Option<HeaderValue>)>> {
+    let mut vary = vec![];
+    for name in response_headers
+        .get_all(VARY)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','))
+        .map(str::trim)
+        .filter(|name| !name.is_empty())
+    {
+        if name == "*" {
+            return None;
+        }
+        if let Ok(name) = HeaderName::try_from(name) {
+            let value = request_headers.get(&name).cloned();
+            vary.push((name, value));
+        }
+    }
+    Some(vary)
+}
+
+#[cfg(all(test, feature = "mock"))]
+mod tests {
+    use super::*;
+    use crate::{BaseClientError, DefaultResponse, Endpoint, Expectation, MockClient, Path, ProblemDetails, Query};
+    use hyper::body::to_bytes;
+    use hyper::http::header::LOCATION;
+
+    async fn get<C: Client>(client: &C, headers: &[(HeaderName, &'static str)]) -> Response<Body> {
+        let mut request = Request::get("http://localhost/items").body(Body::empty()).unwrap();
+        for (name, value) in headers {
+            request.headers_mut().insert(name, HeaderValue::from_static(value));
+        }
+        client.rest(request).await.map_err(|err| format!("{err}")).unwrap()
+    }
+
+    fn cache_status(response: &Response<Body>) -> Option<CacheStatus> {
+        response.extensions().get().copied()
+    }
+
+    fn cached(cache_control: &'static str, times: usize) -> MockClient {
+        let client = MockClient::new();
+        client.expect(
+            Expectation::new(Method::GET, "/items")
+                .times(times)
+                .respond_header(CACHE_CONTROL, HeaderValue::from_static(cache_control))
+                .respond_body("[1, 2]"),
+        );
+        client
+    }
+
+    #[tokio::test]
+    async fn test_fresh_response_is_served_from_cache() {
+        let client = cached("max-age=60", 1).with_middleware(HttpCache::new(LruCacheStore::new(8)));
+
+        assert_eq!(cache_status(&get(&client, &[]).await), Some(CacheStatus::Miss));
+        let response = get(&client, &[]).await;
+        assert_eq!(cache_status(&response), Some(CacheStatus::Fresh));
+        assert_eq!(to_bytes(response.into_body()).await.unwrap(), "[1, 2]");
+    }
+
+    #[tokio::test]
+    async fn test_must_revalidate_is_served_from_cache_while_fresh() {
+        let client = cached("max-age=60, must-revalidate", 1).with_middleware(HttpCache::new(LruCacheStore::new(8)));
+
+        get(&client, &[]).await;
+        assert_eq!(cache_status(&get(&client, &[]).await), Some(CacheStatus::Fresh));
+    }
+
+    #[tokio::test]
+    async fn test_stale_response_is_revalidated() {
+        let client = MockClient::new();
+        client
+            .expect(
+                Expectation::new(Method::GET, "/items")
+                    .times(1)
+                    .respond_header(CACHE_CONTROL, HeaderValue::from_static("max-age=0"))
+                    .respond_header(ETAG, HeaderValue::from_static("\"v1\""))
+                    .respond_body("[1, 2]"),
+            )
+            .expect(
+                Expectation::new(Method::GET, "/items")
+                    .times(1)
+                    .header(IF_NONE_MATCH, HeaderValue::from_static("\"v1\""))
+                    .respond_status(StatusCode::NOT_MODIFIED)
+                    .respond_header(CACHE_CONTROL, HeaderValue::from_static("max-age=60")),
+            );
+        let client = client.with_middleware(HttpCache::new(LruCacheStore::new(8)));
+
+        assert_eq!(cache_status(&get(&client, &[]).await), Some(CacheStatus::Miss));
+
+        let response = get(&client, &[]).await;
+        assert_eq!(cache_status(&response), Some(CacheStatus::Revalidated));
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(to_bytes(response.into_body()).await.unwrap(), "[1, 2]");
+
+        // the 304's headers refresh the cached entry
+        assert_eq!(cache_status(&get(&client, &[]).await), Some(CacheStatus::Fresh));
+    }
+
+    #[tokio::test]
+    async fn test_conditional_request_is_passed_through() {
+        let client = MockClient::new();
+        client
+            .expect(
+                Expectation::new(Method::GET, "/items")
+                    .times(1)
+                    .respond_header(CACHE_CONTROL, HeaderValue::from_static("max-age=60"))
+                    .respond_header(ETAG, HeaderValue::from_static("\"v1\"")),
+            )
+            .expect(
+                Expectation::new(Method::GET, "/items")
+                    .times(1)
+                    .respond_status(StatusCode::NOT_MODIFIED),
+            );
+        let client = client.with_middleware(HttpCache::new(LruCacheStore::new(8)));
+
+        get(&client, &[]).await;
+        let response = get(&client, &[(IF_NONE_MATCH, "\"v1\"")]).await;
+        assert_eq!(response.status(), StatusCode::NOT_MODIFIED);
+    }
+
+    /// a conditional request for the items whose cached copy has the etag `"v1"`
+    #[derive(Debug)]
+    struct GetItems;
+
+    impl Endpoint for GetItems {
+        const METHOD: Method = Method::GET;
+        type Params<'a> = ();
+        type Body<'a> = ();
+        type Response<T> = DefaultResponse<T>;
+        type ErrorBody = ProblemDetails;
+
+        fn path(&self) -> Path {
+            "http://localhost/items".into()
+        }
+        fn params(&self) -> Self::Params<'_> {}
+        fn headers(&self) -> HeaderMap {
+            HeaderMap::from_iter([(IF_NONE_MATCH, HeaderValue::from_static("\"v1\""))])
+        }
+    }
+
+    #[tokio::test]
+    async fn test_query_of_not_modified_response_is_a_hit() {
+        let client = MockClient::new();
+        client.expect(Expectation::new(Method::GET, "/items").respond_status(StatusCode::NOT_MODIFIED));
+        let client = client.with_middleware(HttpCache::new(LruCacheStore::new(8)));
+
+        let result: Result<Vec<u32>, _> = GetItems.query(&client).await;
+        assert!(matches!(result, Err(BaseClientError::NotModified { .. })), "{result:?}");
+        let result: Result<Option<Vec<u32>>, _> = GetItems.optional().query(&client).await;
+        assert!(matches!(result, Err(BaseClientError::NotModified { .. })), "{result:?}");
+        GetItems.ignore().query(&client).await.unwrap();
+        let response = GetItems.raw().query(&client).await.unwrap();
+        assert_eq!(response.status(), StatusCode::NOT_MODIFIED);
+    }
+
+    #[tokio::test]
+    async fn test_query_of_redirect_is_an_error() {
+        let client = MockClient::new();
+        client.expect(
+            Expectation::new(Method::GET, "/items")
+                .respond_status(StatusCode::FOUND)
+                .respond_header(LOCATION, HeaderValue::from_static("/elsewhere")),
+        );
+
+        let result: Result<Vec<u32>, _> = GetItems.query(&client).await;
+        assert!(
+            matches!(
+                result,
+                Err(BaseClientError::Response {
+                    status: StatusCode::FOUND,
+                    ..
+                })
+            ),
+            "{result:?}"
+        );
+        assert!(GetItems.ignore().query(&client).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_private_response_is_not_stored() {
+        let client = cached("private, max-age=60", 2).with_middleware(HttpCache::new(LruCacheStore::new(8)));
+
+        get(&client, &[]).await;
+        assert_eq!(cache_status(&get(&client, &[]).await), Some(CacheStatus::Miss));
+    }
+
+    #[tokio::test]
+    async fn test_authorized_response_is_only_stored_if_public() {
+        let client = cached("max-age=60", 2).with_middleware(HttpCache::new(LruCacheStore::new(8)));
+        get(&client, &[(AUTHORIZATION, "Bearer a")]).await;
+        assert_eq!(
+            cache_status(&get(&client, &[(AUTHORIZATION, "Bearer b")]).await),
+            Some(CacheStatus::Miss)
+        );
+
+        let client = cached("public, max-age=60", 1).with_middleware(HttpCache::new(LruCacheStore::new(8)));
+        get(&client, &[(AUTHORIZATION, "Bearer a")]).await;
+        assert_eq!(
+            cache_status(&get(&client, &[(AUTHORIZATION, "Bearer b")]).await),
+            Some(CacheStatus::Fresh)
+        );
+    }
+}
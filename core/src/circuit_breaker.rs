@@ -0,0 +1,388 @@
+use crate::{BaseClientError, Client, ClientMiddleware};
+use async_trait::async_trait;
+use hyper::{Body, Request, Response, StatusCode, Uri};
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::fmt::Debug;
+use std::sync::Mutex;
+use std::time::Duration;
+use tokio::time::Instant;
+
+#[derive(Clone, Debug)]
+pub struct CircuitBreakerPolicy {
+    /// fraction of failed requests within the window at or above which the circuit opens
+    pub failure_rate_threshold: f64,
+    /// number of most recent requests the failure rate is computed over
+    pub window_size: usize,
+    /// the failure rate is only evaluated once the window holds at least this many requests
+    pub minimum_requests: usize,
+    /// how long the circuit stays open before letting trial requests through
+    pub cool_down: Duration,
+    /// number of trial requests let through while half-open, the circuit closes once all succeed
+    pub half_open_requests: usize,
+    /// response statuses counted as failures, errors returned by the client (network errors,
+    /// timeouts, etc.) are always counted as failures
+    pub failure_statuses: HashSet<StatusCode>,
+}
+
+impl Default for CircuitBreakerPolicy {
+    fn default() -> Self {
+        Self {
+            failure_rate_threshold: 0.5,
+            window_size: 20,
+            minimum_requests: 10,
+            cool_down: Duration::from_secs(30),
+            half_open_requests: 1,
+            failure_statuses: [
+                StatusCode::INTERNAL_SERVER_ERROR,
+                StatusCode::BAD_GATEWAY,
+                StatusCode::SERVICE_UNAVAILABLE,
+                StatusCode::GATEWAY_TIMEOUT,
+            ]
+            .into_iter()
+            .collect(),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum CircuitState {
+    Closed,
+    Open,
+    HalfOpen,
+}
+
+/// CircuitBreaker is a ClientMiddleware keeping a circuit per upstream: Client::base_uri for requests
+/// to the base uri's host, else the request uri's authority; once an upstream's failure rate crosses
+/// the policy's threshold its circuit opens and requests fail fast with BaseClientError::CircuitOpen
+/// until the cool-down elapses; e.g. `client.with_middleware(CircuitBreaker::default())`
+#[derive(Debug, Default)]
+pub struct CircuitBreaker {
+    policy: CircuitBreakerPolicy,
+    circuits: Mutex<HashMap<String, Circuit>>,
+}
+
+#[derive(Debug)]
+struct Circuit {
+    /// incremented on every state transition, outcomes of requests admitted under a previous
+    /// generation have no bearing on the circuit
+    generation: u64,
+    state: State,
+}
+
+#[derive(Debug)]
+enum State {
+    /// outcomes of the most recent requests, true for a failure
+    Closed(VecDeque<bool>),
+    Open(Instant),
+    HalfOpen {
+        in_flight: usize,
+        succeeded: usize,
+    },
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+enum Outcome {
+    Succeeded,
+    Failed,
+    /// the request was dropped before completing, e.g. when timed out by the caller
+    Cancelled,
+}
+
+/// a request admitted by the circuit breaker, tagged with the generation of the circuit it was
+/// admitted under; if dropped before completing it only frees its half-open trial slot
+struct Attempt<'a> {
+    breaker: &'a CircuitBreaker,
+    host: String,
+    generation: u64,
+    completed: bool,
+}
+
+impl CircuitBreaker {
+    pub fn new(policy: CircuitBreakerPolicy) -> Self {
+        Self {
+            policy,
+            circuits: Mutex::default(),
+        }
+    }
+
+    /// the state of an upstream's circuit, `host` is the base uri or authority keying the circuit
+    pub fn state(&self, host: &str) -> CircuitState {
+        match self.circuits.lock().unwrap().get(host).map(|circuit| &circuit.state) {
+            None | Some(State::Closed(_)) => CircuitState::Closed,
+            Some(State::Open(_)) => CircuitState::Open,
+            Some(State::HalfOpen { .. }) => CircuitState::HalfOpen,
+        }
+    }
+
+    /// the generation of the circuit the request is admitted under, None if rejected
+    fn try_acquire(&self, host: &str) -> Option<u64> {
+        let mut circuits = self.circuits.lock().unwrap();
+        let circuit = circuits.entry(host.into()).or_insert_with(|| Circuit {
+            generation: 0,
+            state: State::Closed(VecDeque::default()),
+        });
+        match &mut circuit.state {
+            State::Closed(_) => {}
+            State::Open(until) => {
+                if Instant::now() < *until {
+                    return None;
+                }
+                tracing::info!(host, "circuit half-open");
+                circuit.transition(State::HalfOpen {
+                    in_flight: 1,
+                    succeeded: 0,
+                });
+            }
+            State::HalfOpen { in_flight, succeeded } => {
+                if *in_flight + *succeeded >= self.policy.half_open_requests {
+                    return None;
+                }
+                *in_flight += 1;
+            }
+        }
+        Some(circuit.generation)
+    }
+
+    fn record(&self, host: &str, generation: u64, outcome: Outcome) {
+        let mut circuits = self.circuits.lock().unwrap();
+        let Some(circuit) = circuits
+            .get_mut(host)
+            .filter(|circuit| circuit.generation == generation)
+        else {
+            return;
+        };
+        match &mut circuit.state {
+            State::Closed(_) if outcome == Outcome::Cancelled => {}
+            State::Closed(outcomes) => {
+                outcomes.push_back(outcome == Outcome::Failed);
+                while outcomes.len() > self.policy.window_size.max(1) {
+                    outcomes.pop_front();
+                }
+                if outcomes.len() < self.policy.minimum_requests {
+                    return;
+                }
+                let failure_rate = outcomes.iter().filter(|failed| **failed).count() as f64 / outcomes.len() as f64;
+                if failure_rate >= self.policy.failure_rate_threshold {
+                    tracing::warn!(host, failure_rate, "circuit opened");
+                    circuit.transition(State::Open(Instant::now() + self.policy.cool_down));
+                }
+            }
+            // an open circuit has not admitted any request of its generation
+            State::Open(_) => {}
+            State::HalfOpen { in_flight, succeeded } => {
+                *in_flight = in_flight.saturating_sub(1);
+                match outcome {
+                    Outcome::Cancelled => {}
+                    Outcome::Failed => {
+                        tracing::warn!(host, "circuit reopened");
+                        circuit.transition(State::Open(Instant::now() + self.policy.cool_down));
+                    }
+                    Outcome::Succeeded => {
+                        *succeeded += 1;
+                        if *succeeded >= self.policy.half_open_requests {
+                            tracing::info!(host, "circuit closed");
+                            circuit.transition(State::Closed(VecDeque::default()));
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
+
+impl Circuit {
+    fn transition(&mut self, state: State) {
+        self.generation += 1;
+        self.state = state;
+    }
+}
+
+impl Attempt<'_> {
+    fn complete(mut self, outcome: Outcome) {
+        self.completed = true;
+        self.breaker.record(&self.host, self.generation, outcome);
+    }
+}
+
+impl Drop for Attempt<'_> {
+    fn drop(&mut self) {
+        if !self.completed {
+            self.breaker.record(&self.host, self.generation, Outcome::Cancelled);
+        }
+    }
+}
+
+#[async_trait]
+impl<C: Client + Sync> ClientMiddleware<C> for CircuitBreaker {
+    async fn handle(&self, request: Request<Body>, client: &C) -> Result<Response<Body>, C::Error> {
+        let authority = request.uri().authority();
+        let host = match Client::base_uri(client) {
+            Some(base_uri) if base_uri.parse::<Uri>().ok().as_ref().and_then(Uri::authority) == authority => {
+                base_uri.to_string()
+            }
+            _ => authority.map(|authority| authority.to_string()).unwrap_or_default(),
+        };
+
+        let Some(generation) = self.try_acquire(&host) else {
+            return Err(BaseClientError::CircuitOpen.into());
+        };
+        let attempt = Attempt {
+            breaker: self,
+            host,
+            generation,
+            completed: false,
+        };
+
+        let result = client.rest(request).await;
+        let outcome = match &result {
+            Ok(response) if !self.policy.failure_statuses.contains(&response.status()) => Outcome::Succeeded,
+            _ => Outcome::Failed,
+        };
+        attempt.complete(outcome);
+
+        result
+    }
+}
+
+#[cfg(all(test, feature = "mock"))]
+mod tests {
+    use super::*;
+    use crate::{Expectation, MockClient};
+    use hyper::Method;
+
+    const HOST: &str = "localhost";
+
+    fn breaker() -> CircuitBreaker {
+        CircuitBreaker::new(CircuitBreakerPolicy {
+            window_size: 4,
+            minimum_requests: 4,
+            ..Default::default()
+        })
+    }
+
+    async fn get<C: Client>(client: &C) -> Result<StatusCode, C::Error> {
+        let request = Request::get("http://localhost/items").body(Body::empty()).unwrap();
+        client.rest(request).await.map(|response| response.status())
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_circuit_opens_fails_fast_and_closes_after_trial() {
+        let client = MockClient::new();
+        client
+            .expect(
+                Expectation::new(Method::GET, "/items")
+                    .times(4)
+                    .respond_status(StatusCode::SERVICE_UNAVAILABLE),
+            )
+            .expect(Expectation::new(Method::GET, "/items").times(2));
+        let client = client.with_middleware(breaker());
+
+        for _ in 0..4 {
+            assert_eq!(get(&client).await.unwrap(), StatusCode::SERVICE_UNAVAILABLE);
+        }
+        assert!(matches!(get(&client).await, Err(BaseClientError::CircuitOpen)));
+
+        tokio::time::advance(Duration::from_secs(30)).await;
+        assert_eq!(get(&client).await.unwrap(), StatusCode::OK);
+        assert_eq!(get(&client).await.unwrap(), StatusCode::OK);
+    }
+
+    /// a MockClient with a base uri
+    #[derive(Debug)]
+    struct BaseUriClient(MockClient, &'static str);
+
+    #[async_trait]
+    impl Client for BaseUriClient {
+        type Error = BaseClientError;
+
+        fn headers(&self) -> &hyper::HeaderMap {
+            self.0.headers()
+        }
+        fn base_uri(&self) -> Option<&str> {
+            Some(self.1)
+        }
+        async fn rest(&self, request: Request<Body>) -> Result<Response<Body>, Self::Error> {
+            self.0.rest(request).await
+        }
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_circuits_are_kept_per_base_uri() {
+        let v1 = MockClient::new();
+        v1.expect(
+            Expectation::new(Method::GET, "/v1/items")
+                .times(4)
+                .respond_status(StatusCode::SERVICE_UNAVAILABLE),
+        );
+        let v1 = BaseUriClient(v1, "http://localhost/v1");
+        let v2 = MockClient::new();
+        v2.expect(Expectation::new(Method::GET, "/v2/items").times(1));
+        let v2 = BaseUriClient(v2, "http://localhost/v2");
+
+        let breaker = breaker();
+        let request = |uri| Request::get(uri).body(Body::empty()).unwrap();
+        for _ in 0..4 {
+            breaker.handle(request("http://localhost/v1/items"), &v1).await.unwrap();
+        }
+        assert_eq!(breaker.state("http://localhost/v1"), CircuitState::Open);
+        assert_eq!(breaker.state("http://localhost/v2"), CircuitState::Closed);
+
+        let response = breaker.handle(request("http://localhost/v2/items"), &v2).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_outcomes_of_a_previous_generation_are_ignored() {
+        let breaker = breaker();
+        let stale = breaker.try_acquire(HOST).unwrap();
+        for _ in 0..4 {
+            let generation = breaker.try_acquire(HOST).unwrap();
+            breaker.record(HOST, generation, Outcome::Failed);
+        }
+        assert_eq!(breaker.state(HOST), CircuitState::Open);
+
+        tokio::time::advance(Duration::from_secs(30)).await;
+        let trial = breaker.try_acquire(HOST).unwrap();
+        assert_eq!(breaker.state(HOST), CircuitState::HalfOpen);
+
+        // a request admitted while closed neither closes nor reopens the half-open circuit
+        breaker.record(HOST, stale, Outcome::Failed);
+        assert_eq!(breaker.state(HOST), CircuitState::HalfOpen);
+        assert_eq!(breaker.try_acquire(HOST), None);
+
+        breaker.record(HOST, trial, Outcome::Succeeded);
+        assert_eq!(breaker.state(HOST), CircuitState::Closed);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_dropped_attempt_is_neutral() {
+        let breaker = breaker();
+        for _ in 0..4 {
+            let generation = breaker.try_acquire(HOST).unwrap();
+            drop(Attempt {
+                breaker: &breaker,
+                host: HOST.into(),
+                generation,
+                completed: false,
+            });
+        }
+        assert_eq!(breaker.state(HOST), CircuitState::Closed);
+
+        for _ in 0..4 {
+            let generation = breaker.try_acquire(HOST).unwrap();
+            breaker.record(HOST, generation, Outcome::Failed);
+        }
+        tokio::time::advance(Duration::from_secs(30)).await;
+        let generation = breaker.try_acquire(HOST).unwrap();
+        drop(Attempt {
+            breaker: &breaker,
+            host: HOST.into(),
+            generation,
+            completed: false,
+        });
+
+        // the cancelled trial freed its slot without reopening the circuit
+        assert_eq!(breaker.state(HOST), CircuitState::HalfOpen);
+        assert!(breaker.try_acquire(HOST).is_some());
+    }
+}
@@ -1,19 +1,36 @@
-use crate::{traceparent, TRACEPARENT};
+use crate::{inject_context, AuthGeneration, AuthProvider, GraphQLError, WithAuth};
 use async_trait::async_trait;
 use concat_string::concat_string;
-use hyper::body::to_bytes;
-use hyper::http::header::{HeaderMap, HeaderValue};
-use hyper::http::uri::{InvalidUri, PathAndQuery};
-use hyper::{client::connect::Connect, Body, Method, Request, Response, StatusCode, Uri};
+use futures::future::{poll_fn, BoxFuture, FutureExt};
+use futures::stream::{Stream, StreamExt};
+use hyper::body::{to_bytes, HttpBody};
+use hyper::client::{connect::Connect, HttpConnector};
+use hyper::http::header::{HeaderMap, HeaderValue, CONTENT_TYPE, LINK, RETRY_AFTER};
+use hyper::http::uri::InvalidUri;
+use hyper::{Body, Method, Request, Response, StatusCode, Uri};
 use lazy_static::lazy_static;
 use serde::{
-    de::{DeserializeOwned, Deserializer},
+    de::{DeserializeOwned, Deserializer, IntoDeserializer},
     Deserialize, Serialize,
 };
+use std::any::Any;
+use std::cell::Cell;
+use std::collections::{hash_map::RandomState, BTreeMap, HashSet};
 use std::fmt::{Debug, Display};
+use std::hash::{BuildHasher, Hasher};
+use std::pin::pin;
+use std::sync::Arc;
+use std::task::{ready, Context, Poll};
+use std::time::Duration;
 use std::{borrow::Cow, ops::Deref};
 use thiserror::Error;
+use tokio::time::{timeout_at, Instant};
+use tower_layer::Layer;
+use tower_service::Service;
+use tracing::Instrument;
 
+#[cfg(feature = "metrics")]
+use crate::RequestMetrics;
 #[cfg(feature = "tracing")]
 use tracing::instrument;
 
@@ -29,8 +46,20 @@ impl<E> ClientError for E where E: Debug + Display + From<BaseClientError> + Fro
 pub enum BaseClientError {
     #[error("could not process body, too large")]
     BodyTooLarge,
+    #[error("could not authenticate: {0}")]
+    Auth(String),
+    #[error("circuit open, upstream is unavailable")]
+    CircuitOpen,
+    #[error("graphql errors: {}", .0.iter().map(|error| error.message.as_str()).collect::<Vec<_>>().join("; "))]
+    GraphQL(Vec<GraphQLError>),
+    #[error("invalid header: {0}")]
+    InvalidHeader(String),
     #[error("invalid uri: {0}")]
     InvalidUri(#[from] InvalidUri),
+    /// the upstream answered a conditional request with 304 Not Modified,
+    /// i.e. the caller's copy of the response is still valid
+    #[error("not modified")]
+    NotModified { headers: Box<HeaderMap> },
     #[error("could not send request / receive response")]
     NetworkError(#[from] hyper::Error),
     #[error("could not build body{}", if .0.is_empty() { .0.into() } else { format!(": {}", .0)})]
@@ -39,64 +68,157 @@ pub enum BaseClientError {
     RequestBodySerialization(String),
     #[error("could not serialize request query params: {0}")]
     RequestParamsSerialization(#[from] serde_qs::Error),
+    #[error("rate limited, retry after {retry_after:?}")]
+    RateLimited { retry_after: Duration },
     #[error("status: {status}; message: {message}")]
-    Response { status: StatusCode, message: String },
+    Response {
+        status: StatusCode,
+        message: String,
+        headers: Box<HeaderMap>,
+        /// the response body parsed as the endpoint's Endpoint::ErrorBody, if it could be parsed
+        body: Option<ErrorBody>,
+    },
+    #[error("could not decode response body: {0}")]
+    ResponseBodyDecode(String),
     #[error("could not deserialize response body: {0}")]
     ResponseBodyDeserialization(serde_json::error::Error),
     #[error("{0}")]
     ResponseBodyInvalidCharacter(hyper::Error),
+    #[error("service error: {0}")]
+    Service(BoxError),
+    #[error("request timed out")]
+    Timeout,
+    #[error("invalid tls configuration: {0}")]
+    Tls(String),
 }
 
+pub type BoxError = Box<dyn std::error::Error + Send + Sync>;
+
 impl From<Response<Vec<u8>>> for BaseClientError {
     fn from(response: Response<Vec<u8>>) -> Self {
+        let (mut parts, body) = response.into_parts();
         Self::Response {
-            status: response.status(),
-            message: String::from_utf8_lossy(response.into_body().as_ref()).into(),
+            status: parts.status,
+            message: String::from_utf8_lossy(body.as_ref()).into(),
+            body: parts.extensions.remove::<ErrorBody>(),
+            headers: Box::new(parts.headers),
+        }
+    }
+}
+
+impl BaseClientError {
+    /// whether the request failed in transport, i.e. the connection could not be established or
+    /// broke down, or no response was received in time
+    pub fn is_transport(&self) -> bool {
+        matches!(self, Self::NetworkError(_) | Self::Timeout)
+    }
+
+    /// the parsed error body of an upstream error response, if it was parsed as a `T`
+    pub fn error_body<T: 'static>(&self) -> Option<&T> {
+        match self {
+            Self::Response { body: Some(body), .. } => body.downcast_ref(),
+            _ => None,
         }
     }
+
+    pub fn problem_details(&self) -> Option<&ProblemDetails> {
+        self.error_body()
+    }
+}
+
+/// a type erased Endpoint::ErrorBody, recover the concrete type with ErrorBody::downcast_ref
+pub struct ErrorBody(Box<dyn AnyErrorBody>);
+
+trait AnyErrorBody: Any + Debug + Send + Sync {
+    fn as_any(&self) -> &dyn Any;
+}
+
+impl<T: Any + Debug + Send + Sync> AnyErrorBody for T {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
 }
 
-impl ClientError for BaseClientError {}
+impl Debug for ErrorBody {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        self.0.fmt(f)
+    }
+}
+
+impl ErrorBody {
+    pub fn new<T: Any + Debug + Send + Sync>(body: T) -> Self {
+        Self(Box::new(body))
+    }
+
+    pub fn is<T: 'static>(&self) -> bool {
+        (*self.0).as_any().is::<T>()
+    }
+
+    pub fn downcast_ref<T: 'static>(&self) -> Option<&T> {
+        (*self.0).as_any().downcast_ref()
+    }
+}
+
+/// RFC 7807 problem details, the default Endpoint::ErrorBody
+#[derive(Clone, Debug, Default, Deserialize, Eq, PartialEq, Serialize)]
+pub struct ProblemDetails {
+    #[serde(default, rename = "type", skip_serializing_if = "Option::is_none")]
+    pub type_: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub title: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub status: Option<u16>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub detail: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub instance: Option<String>,
+    /// any extension members included in the problem details
+    #[serde(flatten)]
+    pub extensions: serde_json::Map<String, serde_json::Value>,
+}
 
+/// the path (and optionally query) of an Endpoint relative to the client's base uri, static paths
+/// and paths rendered with `path!` keep their template (without the query) as a low-cardinality
+/// route name labeling the endpoint's request spans and metrics
 #[derive(Clone, Debug)]
-pub struct Path(Cow<'static, str>);
+pub struct Path {
+    path: Cow<'static, str>,
+    template: Option<&'static str>,
+}
 
 impl std::fmt::Display for Path {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        match &self.0 {
-            Cow::Borrowed(str) => write!(f, "{str}"),
-            Cow::Owned(str) => write!(f, "{str}"),
-        }
+        write!(f, "{}", self.path)
     }
 }
 
 impl From<String> for Path {
     fn from(str: String) -> Self {
-        Self(Cow::Owned(str))
+        Self {
+            path: Cow::Owned(str),
+            template: None,
+        }
     }
 }
 
 impl From<&'static str> for Path {
     fn from(str: &'static str) -> Self {
-        Self(Cow::Borrowed(str))
+        Self {
+            path: Cow::Borrowed(str),
+            template: str.split('?').next(),
+        }
     }
 }
 
 impl AsRef<str> for Path {
     fn as_ref(&self) -> &str {
-        match &self.0 {
-            Cow::Borrowed(str) => str,
-            Cow::Owned(str) => str,
-        }
+        &self.path
     }
 }
 
 impl AsRef<[u8]> for Path {
     fn as_ref(&self) -> &[u8] {
-        match &self.0 {
-            Cow::Borrowed(str) => str.as_ref(),
-            Cow::Owned(str) => str.as_ref(),
-        }
+        self.path.as_bytes()
     }
 }
 
@@ -104,33 +226,479 @@ impl Path {
     pub fn as_str(&self) -> &str {
         self.as_ref()
     }
+
+    /// the template the path was rendered from without its query, e.g. `/users/{id}`
+    pub fn template(&self) -> Option<&'static str> {
+        self.template
+    }
+
+    pub fn with_template(self, template: &'static str) -> Self {
+        Self {
+            template: template.split('?').next(),
+            ..self
+        }
+    }
+}
+
+/// renders a Path from a template, percent-encoding each argument with encode_path_segment
+/// and keeping the template next to the rendered path (see Path::template), e.g.
+/// `path!("/orgs/{org}/users/{id}", org = self.org, id = self.id)`;
+/// every placeholder must be passed as a named argument, which is checked at compile time
+#[macro_export]
+macro_rules! path {
+    ($template:literal $(, $name:ident = $value:expr)* $(,)?) => {
+        $crate::Path::from(::std::format!(
+            // expanding the template from concat! keeps format! from capturing unencoded variables
+            ::std::concat!($template)
+            $(, $name = $crate::encode_path_segment(&$value))*
+        ))
+        .with_template($template)
+    };
 }
 
-struct PathAndQueryWrapper(PathAndQuery);
+/// percent-encodes every byte of the segment's Display output except RFC 3986 unreserved characters,
+/// so the value can be interpolated into a path without introducing separators or a query;
+/// `.` and `..` are encoded as well as they would otherwise be removed as dot segments
+pub fn encode_path_segment(segment: impl Display) -> String {
+    let segment = segment.to_string();
+    if segment == "." || segment == ".." {
+        return segment.replace('.', "%2E");
+    }
+    let mut encoded = String::with_capacity(segment.len());
+    for byte in segment.bytes() {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' => encoded.push(byte as char),
+            byte => {
+                encoded.push('%');
+                encoded.push_str(&format!("{byte:02X}"));
+            }
+        }
+    }
+    encoded
+}
 
-impl AsRef<[u8]> for PathAndQueryWrapper {
-    fn as_ref(&self) -> &[u8] {
-        self.0.as_str().as_ref()
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub enum BodyEncoding {
+    /// application/json
+    #[default]
+    Json,
+    /// application/x-www-form-urlencoded, serialized with serde_qs
+    Form,
+    /// multipart/form-data where the body must serialize as a map and each entry is sent as a part:
+    /// strings, numbers and booleans as text/plain, bytes as application/octet-stream, anything else
+    /// as application/json and null entries are skipped; only values serialized as bytes count as
+    /// bytes, e.g. `serde_bytes::ByteBuf` or `bytes::Bytes`, a plain `Vec<u8>` is a json array
+    Multipart,
+    /// the body must serialize as a string (sent as text/plain) or as bytes
+    /// (sent as application/octet-stream), see Multipart for what counts as bytes
+    Raw,
+}
+
+impl BodyEncoding {
+    pub fn encode<T: Serialize>(&self, body: &T) -> Result<(Vec<u8>, HeaderValue), BaseClientError> {
+        match self {
+            Self::Json => Ok((
+                serde_json::to_vec(body).map_err(|err| BaseClientError::RequestBodySerialization(format!("{err}")))?,
+                HeaderValue::from_static("application/json"),
+            )),
+            Self::Form => Ok((
+                serde_qs::to_string(body)
+                    .map_err(|err| BaseClientError::RequestBodySerialization(format!("{err}")))?
+                    .into_bytes(),
+                HeaderValue::from_static("application/x-www-form-urlencoded"),
+            )),
+            Self::Multipart => match body.serialize(PartSerializer { fields: true }) {
+                Ok(Part::Fields(fields)) => encode_multipart(fields),
+                Ok(_) | Err(PartError::Unsupported) => Err(BaseClientError::RequestBodySerialization(
+                    "multipart body encoding requires a body which serializes as a map".into(),
+                )),
+                Err(err) => Err(BaseClientError::RequestBodySerialization(format!("{err}"))),
+            },
+            Self::Raw => match body.serialize(PartSerializer { fields: false }) {
+                Ok(Part::Text(string)) => Ok((
+                    string.into_bytes(),
+                    HeaderValue::from_static("text/plain; charset=utf-8"),
+                )),
+                Ok(Part::Bytes(bytes)) => Ok((bytes, HeaderValue::from_static("application/octet-stream"))),
+                Ok(_) | Err(PartError::Unsupported) => Err(BaseClientError::RequestBodySerialization(
+                    "raw body encoding requires a string or bytes".into(),
+                )),
+                Err(err) => Err(BaseClientError::RequestBodySerialization(format!("{err}"))),
+            },
+        }
+    }
+}
+
+fn encode_multipart(fields: Vec<(String, Part)>) -> Result<(Vec<u8>, HeaderValue), BaseClientError> {
+    let boundary = format!("{:016x}{:016x}", random_u64(), random_u64());
+
+    let mut bytes = vec![];
+    for (name, part) in fields {
+        let (content_type, content) = match part {
+            Part::Skip => continue,
+            Part::Text(string) => ("text/plain; charset=utf-8", string.into_bytes()),
+            Part::Bytes(content) => ("application/octet-stream", content),
+            Part::Json(json) => ("application/json", json),
+            Part::Fields(_) => unreachable!("nested maps are serialized as json parts"),
+        };
+        let name = name.replace('\\', "\\\\").replace('"', "\\\"");
+        bytes.extend_from_slice(
+            concat_string!(
+                "--",
+                boundary,
+                "\r\ncontent-disposition: form-data; name=\"",
+                name,
+                "\"\r\ncontent-type: ",
+                content_type,
+                "\r\n\r\n"
+            )
+            .as_bytes(),
+        );
+        bytes.extend_from_slice(&content);
+        bytes.extend_from_slice(b"\r\n");
+    }
+    bytes.extend_from_slice(concat_string!("--", boundary, "--\r\n").as_bytes());
+
+    let content_type = HeaderValue::from_str(&concat_string!("multipart/form-data; boundary=", boundary))
+        .map_err(|err| BaseClientError::RequestBodySerialization(format!("{err}")))?;
+
+    Ok((bytes, content_type))
+}
+
+/// a raw body or a multipart body's field as classified by PartSerializer
+enum Part {
+    Skip,
+    Text(String),
+    Bytes(Vec<u8>),
+    Json(Vec<u8>),
+    Fields(Vec<(String, Part)>),
+}
+
+#[derive(Debug, Error)]
+enum PartError {
+    /// the value is neither a scalar nor bytes (nor a map if fields are accepted)
+    #[error("unsupported value")]
+    Unsupported,
+    #[error("{0}")]
+    Custom(String),
+}
+
+impl serde::ser::Error for PartError {
+    fn custom<T: Display>(msg: T) -> Self {
+        Self::Custom(msg.to_string())
+    }
+}
+
+/// classifies a value by the serde data model rather than by its json representation so that
+/// bytes are told apart from sequences of integers; maps and structs are accepted as Part::Fields
+/// only if `fields` is set
+struct PartSerializer {
+    fields: bool,
+}
+
+impl PartSerializer {
+    /// classifies a multipart field, falling back to json for values which aren't scalars or bytes
+    fn field<T: Serialize + ?Sized>(value: &T) -> Result<Part, PartError> {
+        match value.serialize(Self { fields: false }) {
+            Err(PartError::Unsupported) => Ok(Part::Json(
+                serde_json::to_vec(value).map_err(|err| PartError::Custom(format!("{err}")))?,
+            )),
+            part => part,
+        }
+    }
+}
+
+macro_rules! serialize_as_text {
+    ($($method:ident: $ty:ty),* $(,)?) => {
+        $(
+            fn $method(self, value: $ty) -> Result<Part, PartError> {
+                Ok(Part::Text(value.to_string()))
+            }
+        )*
+    };
+}
+
+impl serde::Serializer for PartSerializer {
+    type Ok = Part;
+    type Error = PartError;
+    type SerializeSeq = serde::ser::Impossible<Part, PartError>;
+    type SerializeTuple = serde::ser::Impossible<Part, PartError>;
+    type SerializeTupleStruct = serde::ser::Impossible<Part, PartError>;
+    type SerializeTupleVariant = serde::ser::Impossible<Part, PartError>;
+    type SerializeMap = FieldsSerializer;
+    type SerializeStruct = FieldsSerializer;
+    type SerializeStructVariant = serde::ser::Impossible<Part, PartError>;
+
+    serialize_as_text!(
+        serialize_bool: bool,
+        serialize_i8: i8,
+        serialize_i16: i16,
+        serialize_i32: i32,
+        serialize_i64: i64,
+        serialize_i128: i128,
+        serialize_u8: u8,
+        serialize_u16: u16,
+        serialize_u32: u32,
+        serialize_u64: u64,
+        serialize_u128: u128,
+        serialize_f32: f32,
+        serialize_f64: f64,
+        serialize_char: char,
+        serialize_str: &str,
+    );
+
+    fn serialize_bytes(self, value: &[u8]) -> Result<Part, PartError> {
+        Ok(Part::Bytes(value.to_vec()))
+    }
+    fn serialize_none(self) -> Result<Part, PartError> {
+        Ok(Part::Skip)
+    }
+    fn serialize_some<T: Serialize + ?Sized>(self, value: &T) -> Result<Part, PartError> {
+        value.serialize(self)
+    }
+    fn serialize_unit(self) -> Result<Part, PartError> {
+        Ok(Part::Skip)
+    }
+    fn serialize_unit_struct(self, _: &'static str) -> Result<Part, PartError> {
+        Ok(Part::Skip)
+    }
+    fn serialize_unit_variant(self, _: &'static str, _: u32, variant: &'static str) -> Result<Part, PartError> {
+        Ok(Part::Text(variant.into()))
+    }
+    fn serialize_newtype_struct<T: Serialize + ?Sized>(self, _: &'static str, value: &T) -> Result<Part, PartError> {
+        value.serialize(self)
+    }
+    fn serialize_newtype_variant<T: Serialize + ?Sized>(
+        self,
+        _: &'static str,
+        _: u32,
+        _: &'static str,
+        _: &T,
+    ) -> Result<Part, PartError> {
+        Err(PartError::Unsupported)
+    }
+    fn serialize_seq(self, _: Option<usize>) -> Result<Self::SerializeSeq, PartError> {
+        Err(PartError::Unsupported)
+    }
+    fn serialize_tuple(self, _: usize) -> Result<Self::SerializeTuple, PartError> {
+        Err(PartError::Unsupported)
+    }
+    fn serialize_tuple_struct(self, _: &'static str, _: usize) -> Result<Self::SerializeTupleStruct, PartError> {
+        Err(PartError::Unsupported)
+    }
+    fn serialize_tuple_variant(
+        self,
+        _: &'static str,
+        _: u32,
+        _: &'static str,
+        _: usize,
+    ) -> Result<Self::SerializeTupleVariant, PartError> {
+        Err(PartError::Unsupported)
+    }
+    fn serialize_map(self, len: Option<usize>) -> Result<Self::SerializeMap, PartError> {
+        match self.fields {
+            true => Ok(FieldsSerializer::new(len)),
+            false => Err(PartError::Unsupported),
+        }
+    }
+    fn serialize_struct(self, _: &'static str, len: usize) -> Result<Self::SerializeStruct, PartError> {
+        self.serialize_map(Some(len))
+    }
+    fn serialize_struct_variant(
+        self,
+        _: &'static str,
+        _: u32,
+        _: &'static str,
+        _: usize,
+    ) -> Result<Self::SerializeStructVariant, PartError> {
+        Err(PartError::Unsupported)
+    }
+}
+
+struct FieldsSerializer {
+    fields: Vec<(String, Part)>,
+    name: Option<String>,
+}
+
+impl FieldsSerializer {
+    fn new(len: Option<usize>) -> Self {
+        Self {
+            fields: Vec::with_capacity(len.unwrap_or_default()),
+            name: None,
+        }
+    }
+}
+
+impl serde::ser::SerializeMap for FieldsSerializer {
+    type Ok = Part;
+    type Error = PartError;
+
+    fn serialize_key<T: Serialize + ?Sized>(&mut self, key: &T) -> Result<(), PartError> {
+        match key.serialize(PartSerializer { fields: false }) {
+            Ok(Part::Text(name)) => {
+                self.name = Some(name);
+                Ok(())
+            }
+            _ => Err(PartError::Custom("multipart field names must be strings".into())),
+        }
+    }
+    fn serialize_value<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), PartError> {
+        let name = self.name.take().unwrap_or_default();
+        self.fields.push((name, PartSerializer::field(value)?));
+        Ok(())
+    }
+    fn end(self) -> Result<Part, PartError> {
+        Ok(Part::Fields(self.fields))
+    }
+}
+
+impl serde::ser::SerializeStruct for FieldsSerializer {
+    type Ok = Part;
+    type Error = PartError;
+
+    fn serialize_field<T: Serialize + ?Sized>(&mut self, name: &'static str, value: &T) -> Result<(), PartError> {
+        self.fields.push((name.into(), PartSerializer::field(value)?));
+        Ok(())
+    }
+    fn end(self) -> Result<Part, PartError> {
+        Ok(Part::Fields(self.fields))
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub enum Decoder {
+    /// application/json
+    #[default]
+    Json,
+    /// application/x-www-form-urlencoded, deserialized with serde_qs
+    Form,
+    /// the utf-8 body deserialized as a string
+    Text,
+    /// newline delimited json where each non-empty line is an element of a sequence
+    Ndjson,
+    /// application/msgpack
+    #[cfg(feature = "msgpack")]
+    MessagePack,
+    /// chosen from the response's Content-Type header with Decoder::from_content_type,
+    /// falling back to Json when the header is missing or unrecognized
+    ContentType,
+}
+
+impl Decoder {
+    pub fn from_content_type(content_type: &str) -> Option<Self> {
+        let essence = content_type
+            .split(';')
+            .next()
+            .unwrap_or_default()
+            .trim()
+            .to_ascii_lowercase();
+        match essence.as_str() {
+            "application/json" => Some(Self::Json),
+            "application/x-www-form-urlencoded" => Some(Self::Form),
+            "application/x-ndjson" | "application/ndjson" | "application/jsonl" | "application/jsonlines" => {
+                Some(Self::Ndjson)
+            }
+            #[cfg(feature = "msgpack")]
+            "application/msgpack" | "application/x-msgpack" | "application/vnd.msgpack" => Some(Self::MessagePack),
+            essence if essence.ends_with("+json") => Some(Self::Json),
+            essence if essence.starts_with("text/") => Some(Self::Text),
+            _ => None,
+        }
+    }
+
+    pub fn decode<T: DeserializeOwned>(&self, response: &Response<Vec<u8>>) -> Result<T, BaseClientError> {
+        let body = response.body().as_slice();
+        match self {
+            Self::Json => serde_json::from_slice(body).map_err(BaseClientError::ResponseBodyDeserialization),
+            Self::Form => {
+                serde_qs::from_bytes(body).map_err(|err| BaseClientError::ResponseBodyDecode(format!("{err}")))
+            }
+            Self::Text => {
+                let text =
+                    std::str::from_utf8(body).map_err(|err| BaseClientError::ResponseBodyDecode(format!("{err}")))?;
+                T::deserialize(text.into_deserializer())
+                    .map_err(|err: serde::de::value::Error| BaseClientError::ResponseBodyDecode(format!("{err}")))
+            }
+            Self::Ndjson => {
+                let values = body
+                    .split(|byte| *byte == b'\n')
+                    .filter(|line| !line.iter().all(u8::is_ascii_whitespace))
+                    .map(serde_json::from_slice::<serde_json::Value>)
+                    .collect::<Result<Vec<_>, _>>()
+                    .map_err(BaseClientError::ResponseBodyDeserialization)?;
+                serde_json::from_value(serde_json::Value::Array(values))
+                    .map_err(BaseClientError::ResponseBodyDeserialization)
+            }
+            #[cfg(feature = "msgpack")]
+            Self::MessagePack => {
+                rmp_serde::from_slice(body).map_err(|err| BaseClientError::ResponseBodyDecode(format!("{err}")))
+            }
+            Self::ContentType => response
+                .headers()
+                .get(CONTENT_TYPE)
+                .and_then(|content_type| content_type.to_str().ok())
+                .and_then(Self::from_content_type)
+                .unwrap_or(Self::Json)
+                .decode(response),
+        }
     }
 }
 
 pub trait Endpoint {
     const METHOD: Method;
 
-    type Params<'a>: Debug + Send + Serialize = () where Self: 'a;
-    type Response<T> = DefaultResponse<T>;
+    /// low-cardinality name of the endpoint's path used to label client metrics and request spans,
+    /// e.g. `/users/{id}`; the template of Endpoint::path is used if unset (see `path!`),
+    /// else the endpoint's type name
+    const ROUTE: Option<&'static str> = None;
+
+    /// serialized into the request's query, `()` for no params
+    type Params<'a>: Debug + Send + Serialize
+    where
+        Self: 'a;
+    /// serialized according to BODY_ENCODING, `()` for endpoints without a body
+    type Body<'a>: Debug + Send + Serialize
+    where
+        Self: 'a;
+    /// wraps the deserialized response, DefaultResponse<T> for responses which are the result itself
+    type Response<T>;
+
+    /// parsed from 4xx/5xx responses (using the response's Content-Type to pick a Decoder)
+    /// and made available on BaseClientError::Response, ProblemDetails for RFC 7807 error responses
+    type ErrorBody: Debug + DeserializeOwned + Send + Sync + 'static;
+
+    /// determines how successful response bodies are deserialized into Endpoint::Response
+    const DECODER: Decoder = Decoder::Json;
+
+    /// determines how the body is serialized and the Content-Type header sent with it,
+    /// a Content-Type header returned from Endpoint::headers takes precedence
+    const BODY_ENCODING: BodyEncoding = BodyEncoding::Json;
 
     fn path(&self) -> Path;
     fn params(&self) -> Self::Params<'_>;
     fn headers(&self) -> HeaderMap {
         HeaderMap::default()
     }
+    /// the headers sent with the endpoint's requests, Endpoint::headers unless overridden
+    /// to fail the request when a header can't be built, e.g. from an invalid header value
+    fn try_headers(&self) -> Result<HeaderMap, BaseClientError> {
+        Ok(self.headers())
+    }
+
+    /// body is serialized according to BODY_ENCODING, no body is sent if None
+    fn body(&self) -> Option<Self::Body<'_>> {
+        None
+    }
+
+    /// timeout applies to sending the request and then to reading the response body (to each
+    /// attempt when retried, see Endpoint::retry), overrides the client's timeout if set
+    fn timeout(&self) -> Option<Duration> {
+        None
+    }
 
-    /// body returns the opaque Body type because the actual body serialization may vary from endpoint
-    /// to endpoint; e.g. one endpoint may want to use serde_json to serialize body data in json format
-    /// while another may simply want to use the raw contents of a string as the bytes to be sent
-    fn body(&self) -> Body {
-        Body::empty()
+    /// maximum size in bytes of the response body, overrides the client's maximum if set
+    fn max_response_size(&self) -> Option<usize> {
+        None
     }
 
     fn optional(self) -> Optional<Self>
@@ -158,209 +726,864 @@ pub trait Endpoint {
         Paged {
             endpoint: self,
             pagination: pagination.into().unwrap_or_default(),
+            prefetch: false,
         }
     }
+    fn retry(self, policy: impl Into<Option<RetryPolicy>>) -> Retry<Self>
+    where
+        Self: Sized,
+    {
+        Retry {
+            inner: self,
+            policy: policy.into().unwrap_or_default(),
+        }
+    }
+}
+
+/// inserted into the extensions of requests built from an Endpoint,
+/// lets ClientMiddleware tell which endpoint a request was made for
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub struct EndpointName(pub &'static str);
+
+impl EndpointName {
+    pub fn of<E: ?Sized>() -> Self {
+        Self(std::any::type_name::<E>())
+    }
 }
 
+/// inserted into the extensions of requests built from an Endpoint with a timeout,
+/// read by clients which apply the timeout themselves, see Client::applies_timeout
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct RequestTimeout(pub Duration);
+
+/// inserted into the extensions of responses to requests built from an Endpoint,
+/// the uri relative references in the response (e.g. a Link header) are resolved against
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct RequestUri(pub Uri);
+
+/// inserted into a response's extensions by a client applying the request timeout itself,
+/// the deadline reading the response body is bound by
+#[derive(Clone, Copy, Debug)]
+struct ResponseDeadline(Instant);
+
 #[async_trait]
 pub trait Client: Debug {
     type Error: ClientError;
 
     fn headers(&self) -> &HeaderMap;
+    /// default timeout of requests made by this client, see Endpoint::timeout
+    fn timeout(&self) -> Option<Duration> {
+        None
+    }
+    /// default maximum size in bytes of response bodies, see Endpoint::max_response_size
+    fn max_response_size(&self) -> Option<usize> {
+        None
+    }
+    /// the uri endpoint paths are resolved against, may carry a path prefix and default query
+    /// params, e.g. `https://api.example.com/v2?api-version=2`; endpoint paths must be absolute
+    /// uris if None
+    fn base_uri(&self) -> Option<&str> {
+        None
+    }
+    /// consulted for credentials on each request built from an Endpoint, see Client::with_auth
+    fn auth(&self) -> Option<&dyn AuthProvider> {
+        None
+    }
+    /// whether an error returned from Client::rest is a transport error, only transport errors
+    /// are retried by Retry; see BaseClientError::is_transport
+    fn is_transport_error(&self, error: &Self::Error) -> bool {
+        let _ = error;
+        false
+    }
+    /// whether the client applies the RequestTimeout of a request itself, e.g. per attempt as
+    /// Retry does, rather than the timeout bounding the whole call to Client::rest
+    fn applies_timeout(&self) -> bool {
+        false
+    }
     async fn rest(&self, request: Request<Body>) -> Result<Response<Body>, Self::Error>;
+
+    /// authenticates the client's requests with the given AuthProvider, a request rejected
+    /// with 401 Unauthorized is retried once if the provider could refresh its credentials
+    fn with_auth<A>(self, auth: A) -> WithAuth<Self, A>
+    where
+        Self: Sized,
+        A: AuthProvider,
+    {
+        WithAuth { client: self, auth }
+    }
+
+    fn with_middleware<M>(self, middleware: M) -> WithMiddleware<Self, M>
+    where
+        Self: Sized,
+        M: ClientMiddleware<Self>,
+    {
+        WithMiddleware {
+            client: self,
+            middleware,
+        }
+    }
+
+    /// wraps the client's requests in a tower Layer, the layered service's error must be
+    /// convertible into the client's error
+    fn layer<L>(self, layer: L) -> LayeredClient<Self, L::Service>
+    where
+        Self: Sized + Send + Sync + 'static,
+        L: Layer<ClientService<Self>>,
+    {
+        let client = Arc::new(self);
+        LayeredClient {
+            service: layer.layer(ClientService(client.clone())),
+            client,
+        }
+    }
 }
 
+/// the base uri of a client, superseded by Client::base_uri which every ClientBaseUri forwards to
+/// (an empty base uri if the client has none)
+#[deprecated(note = "override Client::base_uri instead")]
 pub trait ClientBaseUri {
     fn base_uri(&self) -> &str;
 }
 
-#[async_trait]
-impl<Connector: 'static + Clone + Connect + Send + Sync> Client for hyper::Client<Connector, Body> {
-    type Error = BaseClientError;
-
-    fn headers(&self) -> &HeaderMap {
-        &EMPTY_HEADER_MAP
-    }
-    async fn rest(&self, request: Request<Body>) -> Result<Response<Body>, Self::Error> {
-        Ok(self.request(request).await?)
+#[allow(deprecated)]
+impl<C: Client + ?Sized> ClientBaseUri for C {
+    fn base_uri(&self) -> &str {
+        Client::base_uri(self).unwrap_or_default()
     }
 }
 
-trait EndpointUri<C: Client>: Endpoint {
-    fn uri(&self, client: &C) -> Result<Uri, C::Error>;
+/// ClientMiddleware intercepts every request sent by a Client wrapped with Client::with_middleware,
+/// a middleware can modify the request, call the inner client and then inspect or modify its response,
+/// or return a response without calling the inner client at all; middlewares are chained by wrapping
+/// a WithMiddleware client again, the last middleware added sees the request first
+#[async_trait]
+pub trait ClientMiddleware<C: Client>: Debug + Send + Sync {
+    async fn handle(&self, request: Request<Body>, client: &C) -> Result<Response<Body>, C::Error>;
 }
 
 #[derive(Clone, Debug)]
-enum SerializedParams<T> {
-    Owned(String),
-    Ref(T),
+pub struct WithMiddleware<C, M> {
+    client: C,
+    middleware: M,
 }
 
-impl<T> AsRef<str> for SerializedParams<T> {
-    default fn as_ref(&self) -> &str {
-        match self {
-            Self::Owned(string) => string.as_ref(),
-            Self::Ref(_) => unreachable!(),
-        }
+impl<C, M> WithMiddleware<C, M> {
+    pub fn into_inner(self) -> C {
+        self.client
     }
 }
 
-impl<T: SealedAsRef<str>> AsRef<str> for SerializedParams<T> {
-    fn as_ref(&self) -> &str {
-        match self {
-            Self::Owned(string) => string.as_ref(),
-            Self::Ref(t) => t.sealed_as_ref(),
-        }
+#[async_trait]
+impl<C, M> Client for WithMiddleware<C, M>
+where
+    C: Client + Send + Sync,
+    M: ClientMiddleware<C>,
+{
+    type Error = C::Error;
+
+    fn headers(&self) -> &HeaderMap {
+        self.client.headers()
+    }
+    fn timeout(&self) -> Option<Duration> {
+        self.client.timeout()
+    }
+    fn max_response_size(&self) -> Option<usize> {
+        self.client.max_response_size()
+    }
+    fn base_uri(&self) -> Option<&str> {
+        Client::base_uri(&self.client)
+    }
+    fn auth(&self) -> Option<&dyn AuthProvider> {
+        self.client.auth()
+    }
+    fn is_transport_error(&self, error: &Self::Error) -> bool {
+        self.client.is_transport_error(error)
+    }
+    fn applies_timeout(&self) -> bool {
+        self.client.applies_timeout()
+    }
+    async fn rest(&self, request: Request<Body>) -> Result<Response<Body>, Self::Error> {
+        self.middleware.handle(request, &self.client).await
     }
 }
 
-trait SealedAsRef<T: ?Sized> {
-    fn sealed_as_ref(&self) -> &T;
-}
+/// ClientService is a tower Service which sends requests with the wrapped Client
+#[derive(Debug)]
+pub struct ClientService<C>(Arc<C>);
 
-impl<T, U: AsRef<T>> SealedAsRef<T> for U {
-    default fn sealed_as_ref(&self) -> &T {
-        self.as_ref()
+impl<C> Clone for ClientService<C> {
+    fn clone(&self) -> Self {
+        Self(self.0.clone())
     }
 }
 
-impl SealedAsRef<str> for () {
-    fn sealed_as_ref(&self) -> &str {
-        ""
+impl<C> ClientService<C> {
+    pub fn new(client: C) -> Self {
+        Self(Arc::new(client))
     }
 }
 
-trait EndpointSerializedParams<C: Client>: Endpoint {
-    fn serialized_params(&self) -> Result<SerializedParams<Self::Params<'_>>, C::Error>;
-}
+impl<C: Client + Send + Sync + 'static> Service<Request<Body>> for ClientService<C> {
+    type Response = Response<Body>;
+    type Error = C::Error;
+    type Future = BoxFuture<'static, Result<Response<Body>, C::Error>>;
+
+    fn poll_ready(&mut self, _: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        Poll::Ready(Ok(()))
+    }
 
-impl<C: Client, E: Endpoint> EndpointSerializedParams<C> for E {
-    default fn serialized_params(&self) -> Result<SerializedParams<Self::Params<'_>>, C::Error> {
-        let params = self.params();
-        serde_qs::to_string(&params)
-            .map(SerializedParams::Owned)
-            .map_err(|err| C::Error::from(BaseClientError::from(err)))
+    fn call(&mut self, request: Request<Body>) -> Self::Future {
+        let client = self.0.clone();
+        async move { client.rest(request).await }.boxed()
     }
 }
 
-impl<C: Client, E: Endpoint> EndpointSerializedParams<C> for E
+/// LayeredClient is produced by Client::layer, requests are sent through the layered
+/// service while headers, timeouts and the base uri are taken from the wrapped client
+#[derive(Derivative)]
+#[derivative(Clone(bound = "S: Clone"), Debug(bound = "C: Debug"))]
+pub struct LayeredClient<C, S> {
+    client: Arc<C>,
+    #[derivative(Debug = "ignore")]
+    service: S,
+}
+
+#[async_trait]
+impl<C, S> Client for LayeredClient<C, S>
 where
-    for<'a> <E as Endpoint>::Params<'a>: SealedAsRef<str>,
+    C: Client + Send + Sync,
+    S: Service<Request<Body>, Response = Response<Body>> + Clone + Send + Sync,
+    S::Future: Send,
+    C::Error: From<S::Error>,
 {
-    fn serialized_params(&self) -> Result<SerializedParams<Self::Params<'_>>, C::Error> {
-        Ok(SerializedParams::Ref(self.params()))
+    type Error = C::Error;
+
+    fn headers(&self) -> &HeaderMap {
+        self.client.headers()
+    }
+    fn timeout(&self) -> Option<Duration> {
+        self.client.timeout()
+    }
+    fn max_response_size(&self) -> Option<usize> {
+        self.client.max_response_size()
+    }
+    fn base_uri(&self) -> Option<&str> {
+        Client::base_uri(&*self.client)
+    }
+    fn auth(&self) -> Option<&dyn AuthProvider> {
+        self.client.auth()
+    }
+    fn is_transport_error(&self, error: &Self::Error) -> bool {
+        self.client.is_transport_error(error)
+    }
+    fn applies_timeout(&self) -> bool {
+        self.client.applies_timeout()
+    }
+    async fn rest(&self, request: Request<Body>) -> Result<Response<Body>, Self::Error> {
+        Ok(call_service(self.service.clone(), request).await?)
     }
 }
 
-impl<C: Client, E: Endpoint + EndpointSerializedParams<C>> EndpointUri<C> for E {
-    default fn uri(&self, _client: &C) -> Result<Uri, C::Error> {
-        let path_uri = self.path();
-        let uri = Uri::try_from(path_uri.as_str()).map_err(BaseClientError::from)?;
-        let serialized_params = self.serialized_params()?;
-        if serialized_params.as_ref() == "" {
-            return Ok(uri);
-        }
-
-        let path_and_query: Option<PathAndQuery> = if let Some(path_and_query) = uri.path_and_query() {
-            if let Some(query) = path_and_query.query() {
-                Some(
-                    concat_string!(path_and_query.path(), "?", query, "&", serialized_params)
-                        .parse()
-                        .map_err(BaseClientError::from)?,
-                )
-            } else {
-                Some(
-                    concat_string!(path_and_query.path(), "?", serialized_params)
-                        .parse()
-                        .map_err(BaseClientError::from)?,
-                )
-            }
-        } else {
-            Some(
-                concat_string!("?", serialized_params)
-                    .parse()
-                    .map_err(BaseClientError::from)?,
-            )
-        };
+/// ServiceClient adapts any tower Service into a Client, service errors are
+/// surfaced as BaseClientError::Service
+#[derive(Derivative)]
+#[derivative(Clone(bound = "S: Clone"), Debug)]
+pub struct ServiceClient<S> {
+    #[derivative(Debug = "ignore")]
+    pub service: S,
+    pub base_uri: Option<String>,
+    pub headers: HeaderMap,
+    pub timeout: Option<Duration>,
+    pub max_response_size: Option<usize>,
+}
 
-        if let Some(path_and_query) = path_and_query {
-            let mut uri_parts = uri.into_parts();
-            uri_parts.path_and_query = Some(path_and_query);
-            Ok(Uri::from_parts(uri_parts).unwrap())
-        } else {
-            Ok(uri)
+impl<S> ServiceClient<S> {
+    pub fn new(service: S) -> Self {
+        Self {
+            service,
+            base_uri: None,
+            headers: HeaderMap::default(),
+            timeout: None,
+            max_response_size: None,
         }
     }
 }
 
-impl<C: Client + ClientBaseUri, E: Endpoint + EndpointSerializedParams<C>> EndpointUri<C> for E {
-    default fn uri(&self, client: &C) -> Result<Uri, C::Error> {
-        let uri = Uri::try_from(concat_string!(client.base_uri(), self.path())).map_err(BaseClientError::from)?;
-        let serialized_params = self.serialized_params()?;
-        if serialized_params.as_ref() == "" {
-            return Ok(uri);
-        }
-
-        let path_and_query: Option<PathAndQuery> = if let Some(path_and_query) = uri.path_and_query() {
-            if let Some(query) = path_and_query.query() {
-                Some(
-                    concat_string!(path_and_query.path(), "?", query, "&", serialized_params)
-                        .parse()
-                        .map_err(BaseClientError::from)?,
-                )
-            } else {
-                Some(
-                    concat_string!(path_and_query.path(), "?", serialized_params)
-                        .parse()
-                        .map_err(BaseClientError::from)?,
-                )
-            }
-        } else {
-            Some(
-                concat_string!("?", serialized_params)
-                    .parse()
-                    .map_err(BaseClientError::from)?,
-            )
-        };
+#[async_trait]
+impl<S> Client for ServiceClient<S>
+where
+    S: Service<Request<Body>, Response = Response<Body>> + Clone + Send + Sync,
+    S::Error: Into<BoxError>,
+    S::Future: Send,
+{
+    type Error = BaseClientError;
 
-        if let Some(path_and_query) = path_and_query {
-            let mut uri_parts = uri.into_parts();
-            uri_parts.path_and_query = Some(path_and_query);
-            Ok(Uri::from_parts(uri_parts).unwrap())
-        } else {
-            Ok(uri)
-        }
+    fn headers(&self) -> &HeaderMap {
+        &self.headers
+    }
+    fn timeout(&self) -> Option<Duration> {
+        self.timeout
+    }
+    fn max_response_size(&self) -> Option<usize> {
+        self.max_response_size
+    }
+    fn base_uri(&self) -> Option<&str> {
+        self.base_uri.as_deref()
+    }
+    fn is_transport_error(&self, error: &Self::Error) -> bool {
+        error.is_transport()
+    }
+    async fn rest(&self, request: Request<Body>) -> Result<Response<Body>, Self::Error> {
+        call_service(self.service.clone(), request)
+            .await
+            .map_err(|err| BaseClientError::Service(err.into()))
     }
 }
 
-trait EndpointRequest: Endpoint {
-    fn request<C: Client>(
-        &self,
-        client: &C,
+async fn call_service<S>(mut service: S, request: Request<Body>) -> Result<S::Response, S::Error>
+where
+    S: Service<Request<Body>>,
+{
+    poll_fn(|cx| service.poll_ready(cx)).await?;
+    service.call(request).await
+}
+
+#[async_trait]
+impl<Connector: 'static + Clone + Connect + Send + Sync> Client for hyper::Client<Connector, Body> {
+    type Error = BaseClientError;
+
+    fn headers(&self) -> &HeaderMap {
+        &EMPTY_HEADER_MAP
+    }
+    fn is_transport_error(&self, error: &Self::Error) -> bool {
+        error.is_transport()
+    }
+    async fn rest(&self, request: Request<Body>) -> Result<Response<Body>, Self::Error> {
+        Ok(self.request(request).await?)
+    }
+}
+
+#[cfg(not(feature = "rustls"))]
+type HttpClientConnector = HttpConnector;
+#[cfg(feature = "rustls")]
+type HttpClientConnector = hyper_rustls::HttpsConnector<HttpConnector>;
+
+/// ClientBuilder configures the connection pool, TCP and (with the `rustls` feature) TLS settings
+/// of the hyper client underlying an HttpClient, e.g.
+/// `ClientBuilder::new("https://api.example.com").pool_max_idle_per_host(8).build()?`
+#[derive(Derivative)]
+#[derivative(Clone, Debug)]
+pub struct ClientBuilder {
+    base_uri: String,
+    headers: HeaderMap,
+    timeout: Option<Duration>,
+    max_response_size: Option<usize>,
+    pool_idle_timeout: Option<Duration>,
+    pool_max_idle_per_host: usize,
+    tcp_keepalive: Option<Duration>,
+    #[cfg(feature = "http2")]
+    http2_prior_knowledge: bool,
+    #[cfg(feature = "rustls")]
+    native_roots: bool,
+    #[cfg(feature = "rustls")]
+    root_certificates_pem: Vec<Vec<u8>>,
+    #[cfg(feature = "rustls")]
+    #[derivative(Debug = "ignore")]
+    client_identity_pem: Option<(Vec<u8>, Vec<u8>)>,
+    #[cfg(feature = "rustls")]
+    https_only: bool,
+}
+
+impl ClientBuilder {
+    pub fn new(base_uri: impl Into<String>) -> Self {
+        Self {
+            base_uri: base_uri.into(),
+            headers: HeaderMap::default(),
+            timeout: None,
+            max_response_size: None,
+            pool_idle_timeout: Some(Duration::from_secs(90)),
+            pool_max_idle_per_host: usize::MAX,
+            tcp_keepalive: None,
+            #[cfg(feature = "http2")]
+            http2_prior_knowledge: false,
+            #[cfg(feature = "rustls")]
+            native_roots: true,
+            #[cfg(feature = "rustls")]
+            root_certificates_pem: vec![],
+            #[cfg(feature = "rustls")]
+            client_identity_pem: None,
+            #[cfg(feature = "rustls")]
+            https_only: false,
+        }
+    }
+
+    /// headers sent with every request built from an Endpoint
+    pub fn headers(self, headers: HeaderMap) -> Self {
+        Self { headers, ..self }
+    }
+
+    pub fn timeout(self, timeout: Duration) -> Self {
+        Self {
+            timeout: Some(timeout),
+            ..self
+        }
+    }
+
+    pub fn max_response_size(self, max_response_size: usize) -> Self {
+        Self {
+            max_response_size: Some(max_response_size),
+            ..self
+        }
+    }
+
+    /// how long idle connections are kept in the pool, defaults to 90s, None keeps them indefinitely
+    pub fn pool_idle_timeout(self, pool_idle_timeout: impl Into<Option<Duration>>) -> Self {
+        Self {
+            pool_idle_timeout: pool_idle_timeout.into(),
+            ..self
+        }
+    }
+
+    pub fn pool_max_idle_per_host(self, pool_max_idle_per_host: usize) -> Self {
+        Self {
+            pool_max_idle_per_host,
+            ..self
+        }
+    }
+
+    /// interval of TCP keepalive probes on idle connections, disabled by default
+    pub fn tcp_keepalive(self, tcp_keepalive: Duration) -> Self {
+        Self {
+            tcp_keepalive: Some(tcp_keepalive),
+            ..self
+        }
+    }
+
+    /// speak HTTP/2 right away instead of negotiating it, required for HTTP/2 over plain TCP
+    #[cfg(feature = "http2")]
+    pub fn http2_prior_knowledge(self) -> Self {
+        Self {
+            http2_prior_knowledge: true,
+            ..self
+        }
+    }
+
+    /// whether the platform's root certificates are trusted, defaults to true
+    #[cfg(feature = "rustls")]
+    pub fn native_roots(self, native_roots: bool) -> Self {
+        Self { native_roots, ..self }
+    }
+
+    /// trusts the PEM encoded CA certificates in addition to (or, without native roots, instead of)
+    /// the platform's root certificates
+    #[cfg(feature = "rustls")]
+    pub fn root_certificates_pem(mut self, pem: impl Into<Vec<u8>>) -> Self {
+        self.root_certificates_pem.push(pem.into());
+        self
+    }
+
+    /// presents the PEM encoded certificate chain and private key (PKCS#8, PKCS#1 or SEC1)
+    /// to servers requesting client authentication, i.e. mTLS
+    #[cfg(feature = "rustls")]
+    pub fn client_identity_pem(self, certificate_chain: impl Into<Vec<u8>>, private_key: impl Into<Vec<u8>>) -> Self {
+        Self {
+            client_identity_pem: Some((certificate_chain.into(), private_key.into())),
+            ..self
+        }
+    }
+
+    /// rejects requests to http uris, defaults to false
+    #[cfg(feature = "rustls")]
+    pub fn https_only(self, https_only: bool) -> Self {
+        Self { https_only, ..self }
+    }
+
+    pub fn build(self) -> Result<HttpClient, BaseClientError> {
+        self.base_uri.parse::<Uri>()?;
+
+        let mut http = HttpConnector::new();
+        http.set_keepalive(self.tcp_keepalive);
+        #[cfg(feature = "rustls")]
+        let connector = {
+            http.enforce_http(false);
+            self.https_connector(http)?
+        };
+        #[cfg(not(feature = "rustls"))]
+        let connector = http;
+
+        let mut builder = hyper::Client::builder();
+        builder
+            .pool_idle_timeout(self.pool_idle_timeout)
+            .pool_max_idle_per_host(self.pool_max_idle_per_host);
+        #[cfg(feature = "http2")]
+        builder.http2_only(self.http2_prior_knowledge);
+
+        Ok(HttpClient {
+            client: builder.build(connector),
+            base_uri: self.base_uri,
+            headers: self.headers,
+            timeout: self.timeout,
+            max_response_size: self.max_response_size,
+        })
+    }
+
+    #[cfg(feature = "rustls")]
+    fn https_connector(&self, http: HttpConnector) -> Result<HttpClientConnector, BaseClientError> {
+        let builder = hyper_rustls::HttpsConnectorBuilder::new().with_tls_config(self.tls_config()?);
+        let builder = if self.https_only { builder.https_only() } else { builder.https_or_http() };
+        #[cfg(feature = "http2")]
+        let connector = builder.enable_http1().enable_http2().wrap_connector(http);
+        #[cfg(not(feature = "http2"))]
+        let connector = builder.enable_http1().wrap_connector(http);
+        Ok(connector)
+    }
+
+    #[cfg(feature = "rustls")]
+    fn tls_config(&self) -> Result<rustls::ClientConfig, BaseClientError> {
+        let mut roots = rustls::RootCertStore::empty();
+        if self.native_roots {
+            let native_certs = rustls_native_certs::load_native_certs()
+                .map_err(|err| BaseClientError::Tls(format!("could not load native root certificates: {err}")))?;
+            let (_, ignored) =
+                roots.add_parsable_certificates(&native_certs.into_iter().map(|cert| cert.0).collect::<Vec<_>>());
+            if ignored > 0 {
+                tracing::debug!(ignored, "ignored unparsable native root certificates");
+            }
+        }
+        for pem in &self.root_certificates_pem {
+            for cert in pem_certificates(pem)? {
+                roots
+                    .add(&cert)
+                    .map_err(|err| BaseClientError::Tls(format!("invalid root certificate: {err}")))?;
+            }
+        }
+
+        let config = rustls::ClientConfig::builder()
+            .with_safe_defaults()
+            .with_root_certificates(roots);
+        match &self.client_identity_pem {
+            Some((certificate_chain, private_key)) => config
+                .with_client_auth_cert(pem_certificates(certificate_chain)?, pem_private_key(private_key)?)
+                .map_err(|err| BaseClientError::Tls(format!("invalid client identity: {err}"))),
+            None => Ok(config.with_no_client_auth()),
+        }
+    }
+}
+
+#[cfg(feature = "rustls")]
+fn pem_certificates(pem: &[u8]) -> Result<Vec<rustls::Certificate>, BaseClientError> {
+    let certs = rustls_pemfile::certs(&mut &*pem)
+        .map_err(|err| BaseClientError::Tls(format!("could not parse PEM certificates: {err}")))?;
+    if certs.is_empty() {
+        return Err(BaseClientError::Tls("no certificates found in PEM".into()));
+    }
+    Ok(certs.into_iter().map(rustls::Certificate).collect())
+}
+
+#[cfg(feature = "rustls")]
+fn pem_private_key(pem: &[u8]) -> Result<rustls::PrivateKey, BaseClientError> {
+    let items = rustls_pemfile::read_all(&mut &*pem)
+        .map_err(|err| BaseClientError::Tls(format!("could not parse PEM private key: {err}")))?;
+    items
+        .into_iter()
+        .find_map(|item| match item {
+            rustls_pemfile::Item::PKCS8Key(key)
+            | rustls_pemfile::Item::RSAKey(key)
+            | rustls_pemfile::Item::ECKey(key) => Some(rustls::PrivateKey(key)),
+            _ => None,
+        })
+        .ok_or_else(|| BaseClientError::Tls("no private key found in PEM".into()))
+}
+
+/// HttpClient is a hyper client with a base uri, built with ClientBuilder
+#[derive(Clone, Debug)]
+pub struct HttpClient {
+    client: hyper::Client<HttpClientConnector, Body>,
+    base_uri: String,
+    headers: HeaderMap,
+    timeout: Option<Duration>,
+    max_response_size: Option<usize>,
+}
+
+impl HttpClient {
+    pub fn builder(base_uri: impl Into<String>) -> ClientBuilder {
+        ClientBuilder::new(base_uri)
+    }
+}
+
+#[async_trait]
+impl Client for HttpClient {
+    type Error = BaseClientError;
+
+    fn headers(&self) -> &HeaderMap {
+        &self.headers
+    }
+    fn timeout(&self) -> Option<Duration> {
+        self.timeout
+    }
+    fn max_response_size(&self) -> Option<usize> {
+        self.max_response_size
+    }
+    fn base_uri(&self) -> Option<&str> {
+        Some(&self.base_uri)
+    }
+    fn is_transport_error(&self, error: &Self::Error) -> bool {
+        error.is_transport()
+    }
+    async fn rest(&self, request: Request<Body>) -> Result<Response<Body>, Self::Error> {
+        Ok(self.client.request(request).await?)
+    }
+}
+
+/// serializes an endpoint's params into a query string, params serializing to a string are taken
+/// to be an already encoded query
+pub(crate) fn serialize_params<T: Serialize>(params: &T) -> Result<String, serde_qs::Error> {
+    serde_qs::to_string(params).or_else(|err| match serde_json::to_value(params) {
+        Ok(serde_json::Value::String(query)) => Ok(query),
+        _ => Err(err),
+    })
+}
+
+/// resolves an endpoint's path against the client's base uri following RFC 3986 section 5.2,
+/// except that paths are always resolved relative to the base uri's path so that the base uri
+/// can carry a path prefix: `https://host/api/v2` or `https://host/api/v2/` and `/users` resolve
+/// to `https://host/api/v2/users`; paths with a scheme replace the base uri entirely
+///
+/// The base uri's query params are sent as defaults, params of the same name in the path's
+/// query or the endpoint's params take precedence
+fn resolve_uri(base_uri: Option<&str>, path: &str, params: &str) -> Result<Uri, BaseClientError> {
+    let reference = path.split_once('#').map_or(path, |(reference, _)| reference);
+    let (reference_path, reference_query) = reference.split_once('?').unwrap_or((reference, ""));
+
+    let base_uri = base_uri.map(Uri::try_from).transpose()?;
+    let absolute = Uri::try_from(reference).ok().filter(|uri| uri.scheme().is_some());
+    let (prefix, path, base_query) = match (absolute, &base_uri) {
+        (Some(uri), _) => (origin(&uri), Cow::Owned(uri.path().to_string()), ""),
+        (None, None) => (String::new(), Cow::Borrowed(reference_path), ""),
+        (None, Some(base_uri)) => {
+            let base_query = base_uri.query().unwrap_or_default();
+            // a network-path reference keeps only the base uri's scheme
+            if let Some(scheme) = base_uri.scheme_str().filter(|_| reference_path.starts_with("//")) {
+                let uri = Uri::try_from(concat_string!(scheme, ":", reference_path))?;
+                (origin(&uri), Cow::Owned(remove_dot_segments(uri.path())), "")
+            } else if reference_path.is_empty() {
+                (origin(base_uri), Cow::Owned(base_uri.path().to_string()), base_query)
+            } else {
+                let base_path = base_uri.path().trim_end_matches('/');
+                let reference_path = reference_path.strip_prefix('/').unwrap_or(reference_path);
+                let path = remove_dot_segments(&concat_string!(base_path, "/", reference_path));
+                (origin(base_uri), Cow::Owned(path), base_query)
+            }
+        }
+    };
+
+    let overridden = reference_query
+        .split('&')
+        .chain(params.split('&'))
+        .filter_map(|param| param.split('=').next())
+        .filter(|name| !name.is_empty())
+        .collect::<HashSet<_>>();
+    let query = base_query
+        .split('&')
+        .filter(|param| !overridden.contains(param.split('=').next().unwrap_or_default()))
+        .chain(reference_query.split('&'))
+        .chain(params.split('&'))
+        .filter(|param| !param.is_empty())
+        .collect::<Vec<_>>()
+        .join("&");
+
+    Ok(if query.is_empty() {
+        Uri::try_from(concat_string!(prefix, path))?
+    } else {
+        Uri::try_from(concat_string!(prefix, path, "?", query))?
+    })
+}
+
+/// the scheme and authority of the uri, e.g. `https://host:8443`
+fn origin(uri: &Uri) -> String {
+    match (uri.scheme_str(), uri.authority()) {
+        (Some(scheme), Some(authority)) => concat_string!(scheme, "://", authority.as_str()),
+        _ => String::new(),
+    }
+}
+
+/// RFC 3986 section 5.2.4, applied to absolute paths
+fn remove_dot_segments(path: &str) -> String {
+    let mut segments = vec![];
+    let mut trailing_slash = false;
+    for segment in path.split('/') {
+        trailing_slash = matches!(segment, "." | "..");
+        match segment {
+            "." => {}
+            ".." => {
+                // the first segment is the empty segment before the path's leading slash
+                if segments.len() > 1 {
+                    segments.pop();
+                }
+            }
+            segment => segments.push(segment),
+        }
+    }
+    if trailing_slash || segments.len() == 1 {
+        segments.push("");
+    }
+    segments.join("/")
+}
+
+/// a 3xx response has no body to decode: a 304 Not Modified answering a conditional request
+/// becomes BaseClientError::NotModified (HttpCache answers the 304s of its own revalidations with
+/// the cached response instead), any other 3xx is a redirect which wasn't followed
+fn redirection_error<C: Client>(response: Response<Vec<u8>>) -> C::Error {
+    if response.status() == StatusCode::NOT_MODIFIED {
+        let headers = Box::new(response.into_parts().0.headers);
+        return BaseClientError::NotModified { headers }.into();
+    }
+    BaseClientError::from(response).into()
+}
+
+/// the uri an Endpoint's requests are sent to along with the route labeling them,
+/// both taken from a single rendering of Endpoint::path
+pub(crate) struct Target {
+    uri: Uri,
+    route: &'static str,
+}
+
+pub(crate) trait EndpointRequest: Endpoint {
+    /// the route is Endpoint::ROUTE, else the template of the endpoint's path, else the endpoint's type name
+    fn target<C: Client>(&self, client: &C) -> Result<Target, C::Error> {
+        let params = serialize_params(&self.params()).map_err(BaseClientError::from)?;
+        let path = self.path();
+        Ok(Target {
+            uri: resolve_uri(Client::base_uri(client), path.as_str(), &params)?,
+            route: Self::ROUTE
+                .or_else(|| path.template())
+                .unwrap_or(EndpointName::of::<Self>().0),
+        })
+    }
+
+    async fn request<C: Client>(
+        &self,
+        client: &C,
         endpoint_uri: &Uri,
         endpoint_headers: &HeaderMap,
         next_page: Option<NextPage>,
     ) -> Result<Request<Body>, C::Error>;
+
+    /// builds and sends the request, a 401 Unauthorized response is retried once with a
+    /// rebuilt request if the client's AuthProvider could refresh its credentials
+    async fn send<C: Client>(
+        &self,
+        client: &C,
+        target: &Target,
+        endpoint_headers: &HeaderMap,
+        next_page: Option<NextPage>,
+        limits: ResponseLimits,
+    ) -> Result<Response<Body>, C::Error> {
+        let request = self
+            .request(client, &target.uri, endpoint_headers, next_page.clone())
+            .await?;
+        let authorized_under = request.extensions().get::<AuthGeneration>().copied();
+        let response = self.send_request(client, request, target.route, limits).await?;
+
+        if response.status() != StatusCode::UNAUTHORIZED {
+            return Ok(response);
+        }
+        let Some(auth) = client.auth() else {
+            return Ok(response);
+        };
+        if !auth.refresh(authorized_under).await? {
+            return Ok(response);
+        }
+
+        let request = self.request(client, &target.uri, endpoint_headers, next_page).await?;
+        self.send_request(client, request, target.route, limits).await
+    }
+
+    /// sends a single request, recording client metrics if enabled
+    async fn send_request<C: Client>(
+        &self,
+        client: &C,
+        mut request: Request<Body>,
+        route: &'static str,
+        limits: ResponseLimits,
+    ) -> Result<Response<Body>, C::Error> {
+        let uri = request.uri().clone();
+        if let Some(timeout) = limits.timeout {
+            request.extensions_mut().insert(RequestTimeout(timeout));
+        }
+        #[cfg(feature = "metrics")]
+        let metrics = RequestMetrics::start(&Self::METHOD, route, Client::base_uri(client));
+
+        let span = tracing::info_span!(
+            "request",
+            otel.name = %concat_string!(Self::METHOD.as_str(), " ", route),
+            http.request.method = %Self::METHOD,
+            http.route = route,
+        );
+        let response = rest(client, request, limits.deadline).instrument(span).await;
+
+        #[cfg(feature = "metrics")]
+        metrics.complete(response.as_ref().ok().map(Response::status));
+
+        let mut response = response?;
+        response.extensions_mut().insert(RequestUri(uri));
+        Ok(response)
+    }
+
+    fn limits<C: Client>(&self, client: &C) -> ResponseLimits {
+        let timeout = self.timeout().or_else(|| client.timeout());
+        ResponseLimits {
+            timeout,
+            deadline: timeout.map(|timeout| Instant::now() + timeout),
+            max_response_size: self.max_response_size().or_else(|| client.max_response_size()),
+        }
+    }
+
+    /// attaches the parsed Endpoint::ErrorBody to an error response's extensions
+    /// so that it's picked up when converting the response into a BaseClientError
+    fn error_response(&self, mut response: Response<Vec<u8>>) -> Response<Vec<u8>> {
+        if let Ok(body) = Decoder::ContentType.decode::<Self::ErrorBody>(&response) {
+            response.extensions_mut().insert(ErrorBody::new(body));
+        }
+        response
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+pub(crate) struct ResponseLimits {
+    timeout: Option<Duration>,
+    deadline: Option<Instant>,
+    max_response_size: Option<usize>,
 }
 
 impl<E: Endpoint> EndpointRequest for E {
-    fn request<C: Client>(
+    async fn request<C: Client>(
         &self,
         client: &C,
         endpoint_uri: &Uri,
         endpoint_headers: &HeaderMap,
         next_page: Option<NextPage>,
     ) -> Result<Request<Body>, C::Error> {
-        let uri = match next_page.as_ref() {
+        let uri = match next_page {
             Some(NextPage::FullUri(uri)) => uri,
-            None => endpoint_uri,
+            Some(NextPage::Params(params)) => merge_params(endpoint_uri, &params)?,
+            None => endpoint_uri.clone(),
         };
 
         let request = Request::builder().uri(uri).method(E::METHOD);
 
+        let (body, content_type) = match self.body() {
+            Some(body) => {
+                let (body, content_type) = E::BODY_ENCODING.encode(&body)?;
+                (Body::from(body), Some(content_type))
+            }
+            None => (Body::empty(), None),
+        };
+
         let mut request = request
-            .body(self.body())
+            .body(body)
             .map_err(|e| BaseClientError::RequestBodyBuild(format!("{e}")))?;
+        request.extensions_mut().insert(EndpointName::of::<E>());
 
         let headers = request.headers_mut();
         for (header_name, header_value) in client.headers().iter() {
@@ -369,9 +1592,16 @@ impl<E: Endpoint> EndpointRequest for E {
         for (header_name, header_value) in endpoint_headers.iter() {
             headers.append(header_name, header_value.clone());
         }
+        if let Some(content_type) = content_type {
+            if !headers.contains_key(CONTENT_TYPE) {
+                headers.insert(CONTENT_TYPE, content_type);
+            }
+        }
 
-        if let Some(traceparent) = traceparent() {
-            headers.append(&TRACEPARENT, HeaderValue::from_str(&traceparent).unwrap());
+        inject_context(headers);
+
+        if let Some(auth) = client.auth() {
+            auth.authorize(&mut request).await?;
         }
 
         Ok(request)
@@ -386,6 +1616,67 @@ where
     async fn query(&self, client: &C) -> Result<T, C::Error>;
 }
 
+/// runs the queries against the client with at most `concurrency` in flight at once, returning
+/// their results in input order; fails fast, i.e. the first error to occur is returned and any queries
+/// still in flight are dropped, see query_all_settled to instead wait on every query
+///
+/// this is the bounded counterpart to try_join_all_safe for fanning out queries, each query
+/// runs in its own span recording its index in the input
+pub async fn query_all<C, Q, T>(
+    client: &C,
+    queries: impl IntoIterator<Item = Q>,
+    concurrency: usize,
+) -> Result<Vec<T>, C::Error>
+where
+    C: Client + Sync,
+    Q: Query<C, T> + Send,
+{
+    let mut results = vec![];
+    let mut stream = pin!(query_stream(client, queries, concurrency));
+    while let Some((index, result)) = stream.next().await {
+        if results.len() <= index {
+            results.resize_with(index + 1, || None);
+        }
+        results[index] = Some(result?);
+    }
+    Ok(results.into_iter().flatten().collect())
+}
+
+/// like query_all but runs every query to completion and returns each query's result in input order
+pub async fn query_all_settled<C, Q, T>(
+    client: &C,
+    queries: impl IntoIterator<Item = Q>,
+    concurrency: usize,
+) -> Vec<Result<T, C::Error>>
+where
+    C: Client + Sync,
+    Q: Query<C, T> + Send,
+{
+    let mut results: Vec<_> = query_stream(client, queries, concurrency).collect().await;
+    results.sort_by_key(|(index, _)| *index);
+    results.into_iter().map(|(_, result)| result).collect()
+}
+
+/// runs the queries concurrently, yielding each query's result along with its index in the input
+/// as soon as it completes
+fn query_stream<'a, C, Q, T>(
+    client: &'a C,
+    queries: impl IntoIterator<Item = Q>,
+    concurrency: usize,
+) -> impl Stream<Item = (usize, Result<T, C::Error>)> + 'a
+where
+    C: Client + Sync,
+    Q: Query<C, T> + Send + 'a,
+{
+    let queries = queries.into_iter().enumerate().collect::<Vec<_>>();
+    futures::stream::iter(queries)
+        .map(move |(index, query)| {
+            let span = tracing::info_span!("query", index);
+            async move { (index, query.query(client).await) }.instrument(span)
+        })
+        .buffer_unordered(concurrency.max(1))
+}
+
 #[async_trait]
 impl<E, C, T> Query<C, T> for E
 where
@@ -397,24 +1688,26 @@ where
     #[framed]
     #[cfg_attr(feature = "tracing", instrument(err(Debug)))]
     async fn query(&self, client: &C) -> Result<T, C::Error> {
-        let uri = self.uri(client)?;
-        let headers = self.headers();
+        let target = self.target(client)?;
+        let headers = self.try_headers()?;
 
-        let request = self.request(client, &uri, &headers, None)?;
-
-        let response = client.rest(request).await?;
+        let limits = self.limits(client);
+        let response = self.send(client, &target, &headers, None, limits).await?;
 
         let status = response.status();
         if status.is_client_error() || status.is_server_error() {
-            return Err(C::Error::from(raw_response::<C>(response).await?));
+            return Err(C::Error::from(
+                self.error_response(raw_response::<C>(response, limits).await?),
+            ));
+        }
+        if status.is_redirection() {
+            return Err(redirection_error::<C>(raw_response::<C>(response, limits).await?));
         }
 
-        Ok(
-            serde_json::from_slice::<E::Response<T>>(raw_response::<C>(response).await?.into_body().as_slice())
-                .map_err(BaseClientError::ResponseBodyDeserialization)
-                .map_err(C::Error::from)?
-                .unwrap_response(),
-        )
+        Ok(E::DECODER
+            .decode::<E::Response<T>>(&raw_response::<C>(response, limits).await?)
+            .map_err(C::Error::from)?
+            .unwrap_response())
     }
 }
 
@@ -432,6 +1725,9 @@ impl<E: Endpoint> Optional<E> {
     pub fn ignore(self) -> Ignore<Self> {
         Ignore(self)
     }
+    pub fn retry(self, policy: impl Into<Option<RetryPolicy>>) -> Retry<Self> {
+        Retry::new(policy, self)
+    }
 }
 
 #[async_trait]
@@ -445,24 +1741,28 @@ where
     #[framed]
     #[cfg_attr(feature = "tracing", instrument(err(Debug)))]
     async fn query(&self, client: &C) -> Result<Option<T>, C::Error> {
-        let uri = self.uri(client)?;
-        let headers = self.headers();
-
-        let request = self.request(client, &uri, &headers, None)?;
+        let target = self.target(client)?;
+        let headers = self.try_headers()?;
 
-        let response = client.rest(request).await?;
+        let limits = self.limits(client);
+        let response = self.send(client, &target, &headers, None, limits).await?;
 
         let status = response.status();
         if status == StatusCode::NOT_FOUND {
             return Ok(None);
         }
         if status.is_client_error() || status.is_server_error() {
-            return Err(C::Error::from(raw_response::<C>(response).await?));
+            return Err(C::Error::from(
+                self.error_response(raw_response::<C>(response, limits).await?),
+            ));
+        }
+        if status.is_redirection() {
+            return Err(redirection_error::<C>(raw_response::<C>(response, limits).await?));
         }
 
         Ok(Some(
-            serde_json::from_slice::<E::Response<T>>(raw_response::<C>(response).await?.into_body().as_slice())
-                .map_err(BaseClientError::ResponseBodyDeserialization)
+            E::DECODER
+                .decode::<E::Response<T>>(&raw_response::<C>(response, limits).await?)
                 .map_err(C::Error::from)?
                 .unwrap_response(),
         ))
@@ -483,6 +1783,9 @@ impl<E: Endpoint> Ignore<E> {
     pub fn optional(self) -> Optional<Self> {
         Optional(self)
     }
+    pub fn retry(self, policy: impl Into<Option<RetryPolicy>>) -> Retry<Self> {
+        Retry::new(policy, self)
+    }
 }
 
 #[async_trait]
@@ -494,16 +1797,21 @@ where
     #[framed]
     #[cfg_attr(feature = "tracing", instrument(err(Debug)))]
     async fn query(&self, client: &C) -> Result<(), C::Error> {
-        let uri = self.uri(client)?;
-        let headers = self.headers();
+        let target = self.target(client)?;
+        let headers = self.try_headers()?;
 
-        let request = self.request(client, &uri, &headers, None)?;
-
-        let response = client.rest(request).await?;
+        let limits = self.limits(client);
+        let response = self.send(client, &target, &headers, None, limits).await?;
 
         let status = response.status();
         if status.is_client_error() || status.is_server_error() {
-            return Err(C::Error::from(raw_response::<C>(response).await?));
+            return Err(C::Error::from(
+                self.error_response(raw_response::<C>(response, limits).await?),
+            ));
+        }
+        // a 304 Not Modified is a hit, an ignored response has nothing to decode
+        if status.is_redirection() && status != StatusCode::NOT_MODIFIED {
+            return Err(redirection_error::<C>(raw_response::<C>(response, limits).await?));
         }
 
         Ok(())
@@ -520,6 +1828,12 @@ impl<E> Deref for Raw<E> {
     }
 }
 
+impl<E: Endpoint> Raw<E> {
+    pub fn retry(self, policy: impl Into<Option<RetryPolicy>>) -> Retry<Self> {
+        Retry::new(policy, self)
+    }
+}
+
 #[async_trait]
 impl<E, C> Query<C, Response<Vec<u8>>> for Raw<E>
 where
@@ -529,30 +1843,202 @@ where
     #[framed]
     #[cfg_attr(feature = "tracing", instrument(err(Debug)))]
     async fn query(&self, client: &C) -> Result<Response<Vec<u8>>, C::Error> {
-        let uri = self.uri(client)?;
-        let headers = self.headers();
+        let target = self.target(client)?;
+        let headers = self.try_headers()?;
 
-        let request = self.request(client, &uri, &headers, None)?;
-
-        let response = client.rest(request).await?;
+        let limits = self.limits(client);
+        let response = self.send(client, &target, &headers, None, limits).await?;
 
         let status = response.status();
         if status.is_client_error() || status.is_server_error() {
-            return Err(C::Error::from(raw_response::<C>(response).await?));
+            return Err(C::Error::from(
+                self.error_response(raw_response::<C>(response, limits).await?),
+            ));
         }
+        // 3xx responses, e.g. a 304 Not Modified answering a conditional request, are returned as is
 
-        raw_response::<C>(response).await
+        raw_response::<C>(response, limits).await
     }
 }
 
+/// Pageable can be derived with one of the built-in pagination strategies, e.g.
+/// `#[derive(Pageable)] #[pageable(cursor(field = "/meta/next_cursor", param = "cursor"))]`,
+/// see the NextPage constructors for the available strategies
 #[allow(unused)]
 pub trait Pageable: Endpoint {
-    fn next_page(prev_response: &Response<Vec<u8>>) -> Option<NextPage>;
+    fn next_page(prev_response: &Response<Vec<u8>>, prev_page: &PrevPage) -> Option<NextPage>;
 }
 
 #[derive(Clone, Debug)]
 pub enum NextPage {
     FullUri(Uri),
+    /// query params which are merged into the endpoint's serialized params,
+    /// replacing any params with the same name
+    Params(Vec<(String, String)>),
+}
+
+/// describes the pages fetched so far when requesting the next page
+#[derive(Clone, Copy, Debug)]
+pub struct PrevPage {
+    /// zero-based index of the previous page
+    pub index: usize,
+    /// number of items in the previous page
+    pub len: usize,
+    /// number of items in all pages fetched so far
+    pub total: usize,
+}
+
+impl NextPage {
+    /// uses the uri of the RFC 5988 `Link` header entry with `rel="next"`, a relative uri is
+    /// resolved against the previous request's uri (see RequestUri)
+    pub fn link_header(prev_response: &Response<Vec<u8>>) -> Option<NextPage> {
+        let request_uri = prev_response.extensions().get::<RequestUri>();
+        prev_response
+            .headers()
+            .get_all(LINK)
+            .iter()
+            .filter_map(|header_value| header_value.to_str().ok())
+            .flat_map(split_links)
+            .find_map(|link| {
+                let (uri, link_params) = link.trim().split_once(';')?;
+                let uri = uri.trim().strip_prefix('<')?.strip_suffix('>')?;
+                let is_next = link_params
+                    .split(';')
+                    .any(|link_param| match link_param.split_once('=') {
+                        Some((name, value)) => {
+                            name.trim().eq_ignore_ascii_case("rel")
+                                && value
+                                    .trim()
+                                    .trim_matches('"')
+                                    .split_whitespace()
+                                    .any(|rel| rel.eq_ignore_ascii_case("next"))
+                        }
+                        None => false,
+                    });
+                if !is_next {
+                    return None;
+                }
+                match request_uri {
+                    Some(RequestUri(request_uri)) => resolve_reference(request_uri, uri),
+                    None => uri.parse().ok(),
+                }
+                .map(NextPage::FullUri)
+            })
+    }
+
+    /// reads a cursor token from the json response body and sends it as the query param `param`,
+    /// `field` is either a top level field name or a JSON pointer such as `/meta/next_cursor`
+    pub fn cursor(prev_response: &Response<Vec<u8>>, field: &str, param: &str) -> Option<NextPage> {
+        let body = serde_json::from_slice::<serde_json::Value>(prev_response.body()).ok()?;
+        let cursor = if field.starts_with('/') { body.pointer(field) } else { body.get(field) }?;
+        let cursor = match cursor {
+            serde_json::Value::String(cursor) if !cursor.is_empty() => cursor.clone(),
+            serde_json::Value::Number(cursor) => cursor.to_string(),
+            _ => return None,
+        };
+        Some(NextPage::Params(vec![(param.into(), cursor)]))
+    }
+
+    /// sends the number of items fetched so far as the query param `param`, pagination stops once
+    /// a page is empty or, if `page_size` is provided, once a page has fewer than `page_size` items;
+    /// no limit param is sent, an endpoint requesting a page size sends it itself, e.g. as a query
+    /// field, for it to apply to every page
+    pub fn offset(prev_page: &PrevPage, param: &str, page_size: Option<usize>) -> Option<NextPage> {
+        if prev_page.len == 0 || page_size.map(|page_size| prev_page.len < page_size).unwrap_or_default() {
+            return None;
+        }
+        Some(NextPage::Params(vec![(param.into(), prev_page.total.to_string())]))
+    }
+
+    /// sends the number of the next page as the query param `param` where `first_page` is the number
+    /// of the first page, pagination stops once a page is empty
+    pub fn page_number(prev_page: &PrevPage, param: &str, first_page: usize) -> Option<NextPage> {
+        if prev_page.len == 0 {
+            return None;
+        }
+        Some(NextPage::Params(vec![(
+            param.into(),
+            (first_page + prev_page.index + 1).to_string(),
+        )]))
+    }
+}
+
+/// resolves a uri reference against a base uri following RFC 3986 section 5.2
+fn resolve_reference(base: &Uri, reference: &str) -> Option<Uri> {
+    let reference = reference.split_once('#').map_or(reference, |(reference, _)| reference);
+    if Uri::try_from(reference).is_ok_and(|uri| uri.scheme().is_some()) {
+        return reference.parse().ok();
+    }
+    if let Some(reference) = reference.strip_prefix("//") {
+        return concat_string!(base.scheme_str()?, "://", reference).parse().ok();
+    }
+
+    let (path, query) = reference
+        .split_once('?')
+        .map_or((reference, None), |(path, query)| (path, Some(query)));
+    let (path, query) = match path {
+        "" => (base.path().to_owned(), query.or(base.query())),
+        path if path.starts_with('/') => (remove_dot_segments(path), query),
+        path => {
+            let base_path = base.path();
+            let merged = concat_string!(&base_path[..=base_path.rfind('/').unwrap_or(0)], path);
+            (remove_dot_segments(&merged), query)
+        }
+    };
+    match query {
+        Some(query) => concat_string!(origin(base), path, "?", query),
+        None => concat_string!(origin(base), path),
+    }
+    .parse()
+    .ok()
+}
+
+/// splits a Link header value into its comma separated links,
+/// ignoring commas within the uri or within quoted params
+fn split_links(header_value: &str) -> impl Iterator<Item = &str> {
+    let mut links = vec![];
+    let (mut start, mut in_uri, mut in_quotes) = (0, false, false);
+    for (i, char) in header_value.char_indices() {
+        match char {
+            '<' if !in_quotes => in_uri = true,
+            '>' if !in_quotes => in_uri = false,
+            '"' if !in_uri => in_quotes = !in_quotes,
+            ',' if !in_uri && !in_quotes => {
+                links.push(&header_value[start..i]);
+                start = i + 1;
+            }
+            _ => {}
+        }
+    }
+    links.push(&header_value[start..]);
+    links.into_iter()
+}
+
+pub(crate) fn merge_params(uri: &Uri, params: &[(String, String)]) -> Result<Uri, BaseClientError> {
+    let params = serde_qs::to_string(
+        &params
+            .iter()
+            .map(|(name, value)| (name.as_str(), value.as_str()))
+            .collect::<BTreeMap<_, _>>(),
+    )?;
+    let param_names = params
+        .split('&')
+        .filter_map(|param| param.split('=').next())
+        .collect::<HashSet<_>>();
+
+    let query = uri
+        .query()
+        .into_iter()
+        .flat_map(|query| query.split('&'))
+        .filter(|param| !param.is_empty())
+        .filter(|param| !param_names.contains(param.split('=').next().unwrap_or_default()))
+        .chain(std::iter::once(params.as_str()))
+        .collect::<Vec<_>>()
+        .join("&");
+
+    let mut uri_parts = uri.clone().into_parts();
+    uri_parts.path_and_query = Some(concat_string!(uri.path(), "?", query).parse()?);
+    Ok(Uri::from_parts(uri_parts).unwrap())
 }
 
 #[derive(Clone, Debug, Default)]
@@ -566,17 +2052,23 @@ pub enum Pagination {
 pub struct Paged<E> {
     endpoint: E,
     pagination: Pagination,
+    prefetch: bool,
 }
 
 impl<E> Paged<E> {
     pub fn new(pagination: Pagination, endpoint: E) -> Self {
-        Paged { endpoint, pagination }
+        Paged {
+            endpoint,
+            pagination,
+            prefetch: false,
+        }
     }
 
     pub fn all(endpoint: E) -> Self {
         Paged {
             pagination: Pagination::All,
             endpoint,
+            prefetch: false,
         }
     }
 
@@ -584,6 +2076,132 @@ impl<E> Paged<E> {
         Paged {
             pagination: Pagination::Limit(limit),
             endpoint,
+            prefetch: false,
+        }
+    }
+
+    /// when streaming, starts requesting the next page as soon as the current page is received
+    /// rather than once the current page has been drained
+    pub fn prefetch(self) -> Self {
+        Paged { prefetch: true, ..self }
+    }
+
+    pub fn retry(self, policy: impl Into<Option<RetryPolicy>>) -> Retry<Self> {
+        Retry::new(policy, self)
+    }
+}
+
+impl<E: Endpoint + Pageable> Paged<E> {
+    async fn page<C, T>(&self, client: &C, next_page: Option<NextPage>) -> PageResult<T, C::Error>
+    where
+        C: Client,
+        E::Response<Vec<T>>: DeserializeOwned + UnwrapResponse<Vec<T>>,
+    {
+        let target = self.endpoint.target(client)?;
+        let headers = self.endpoint.try_headers()?;
+
+        let limits = self.endpoint.limits(client);
+        let response = self.endpoint.send(client, &target, &headers, next_page, limits).await?;
+        let response = raw_response::<C>(response, limits).await?;
+
+        let status = response.status();
+        if status.is_client_error() || status.is_server_error() {
+            return Err(C::Error::from(self.endpoint.error_response(response)));
+        }
+        if status.is_redirection() {
+            return Err(redirection_error::<C>(response));
+        }
+
+        let results = E::DECODER
+            .decode::<E::Response<Vec<T>>>(&response)
+            .map_err(C::Error::from)?
+            .unwrap_response();
+
+        Ok((results, response))
+    }
+
+    /// streams the results of each page, the next page is only requested once the
+    /// previous page's results have been consumed (see Paged::prefetch)
+    pub fn stream<'a, C, T>(&'a self, client: &'a C) -> impl Stream<Item = Result<T, C::Error>> + Send + 'a
+    where
+        E: Sync,
+        C: Client + Sync,
+        T: Send + 'a,
+        E::Response<Vec<T>>: DeserializeOwned + UnwrapResponse<Vec<T>>,
+    {
+        let mut state = PagedStream {
+            items: vec![].into_iter(),
+            next: Some(Box::pin(self.page(client, None))),
+            prefetched: None,
+            prev_page: None,
+            remaining: match &self.pagination {
+                Pagination::All => usize::MAX,
+                Pagination::Limit(limit) => *limit,
+            },
+        };
+
+        futures::stream::poll_fn(move |cx| loop {
+            if state.remaining == 0 {
+                return Poll::Ready(None);
+            }
+            if let Some(item) = state.items.next() {
+                state.remaining -= 1;
+                if self.prefetch {
+                    state.poll_prefetch(cx);
+                }
+                return Poll::Ready(Some(Ok(item)));
+            }
+
+            let page = match (state.prefetched.take(), state.next.as_mut()) {
+                (Some(page), _) => page,
+                (None, Some(next)) => {
+                    let page = ready!(next.poll_unpin(cx));
+                    state.next = None;
+                    page
+                }
+                (None, None) => return Poll::Ready(None),
+            };
+            let (results, response) = match page {
+                Ok(page) => page,
+                Err(err) => {
+                    state.remaining = 0;
+                    return Poll::Ready(Some(Err(err)));
+                }
+            };
+
+            let prev_page = PrevPage {
+                index: state.prev_page.map(|prev_page| prev_page.index + 1).unwrap_or_default(),
+                len: results.len(),
+                total: state.prev_page.map(|prev_page| prev_page.total).unwrap_or_default() + results.len(),
+            };
+            state.next = E::next_page(&response, &prev_page)
+                .map(|next_page| Box::pin(self.page(client, Some(next_page))) as BoxFuture<_>);
+            state.prev_page = Some(prev_page);
+            state.items = results.into_iter();
+        })
+    }
+}
+
+/// a page's results along with the raw response they were parsed from
+type PageResult<T, E> = Result<(Vec<T>, Response<Vec<u8>>), E>;
+
+struct PagedStream<'a, T, E> {
+    items: std::vec::IntoIter<T>,
+    next: Option<BoxFuture<'a, PageResult<T, E>>>,
+    prefetched: Option<PageResult<T, E>>,
+    prev_page: Option<PrevPage>,
+    remaining: usize,
+}
+
+impl<T, E> PagedStream<'_, T, E> {
+    /// polls the next page's request with the stream's context without waiting on it, the request
+    /// makes progress each time the stream is polled and wakes the stream's task when it can
+    fn poll_prefetch(&mut self, cx: &mut Context<'_>) {
+        if let Some(next) = self.next.as_mut() {
+            if let Poll::Ready(page) = next.poll_unpin(cx) {
+                self.prefetched = Some(page);
+                self.next = None;
+            }
         }
     }
 }
@@ -604,52 +2222,38 @@ where
             Pagination::Limit(limit) => (*limit, Vec::with_capacity(*limit)),
         };
 
-        let uri = self.endpoint.uri(client)?;
-        let headers = self.endpoint.headers();
-
-        let request = self.endpoint.request(client, &uri, &headers, None)?;
-
-        let response = raw_response::<C>(client.rest(request).await?).await?;
-
-        let status = response.status();
-        if status.is_client_error() || status.is_server_error() {
-            return Err(C::Error::from(response));
-        }
-
-        let mut results = serde_json::from_slice::<E::Response<Vec<T>>>(response.body().as_slice())
-            .map_err(BaseClientError::ResponseBodyDeserialization)
-            .map_err(C::Error::from)?
-            .unwrap_response();
+        let (mut results, mut prev_response) = self.page(client, None).await?;
 
+        let len = results.len();
         all_results.append(&mut results);
 
-        let mut prev_response = response;
+        let mut prev_page = PrevPage {
+            index: 0,
+            len,
+            total: all_results.len(),
+        };
 
         while all_results.len() < limit {
-            let next_page = match E::next_page(&prev_response) {
+            let next_page = match E::next_page(&prev_response, &prev_page) {
                 Some(next_page) => next_page,
                 None => break,
             };
 
-            let request = self.endpoint.request(client, &uri, &headers, Some(next_page))?;
-
-            let response = raw_response::<C>(client.rest(request).await?).await?;
-
-            let status = response.status();
-            if status.is_client_error() || status.is_server_error() {
-                return Err(C::Error::from(response));
-            }
-
-            let mut results = serde_json::from_slice::<E::Response<Vec<T>>>(response.body().as_slice())
-                .map_err(BaseClientError::ResponseBodyDeserialization)
-                .map_err(C::Error::from)?
-                .unwrap_response();
+            let (mut results, response) = self.page(client, Some(next_page)).await?;
 
+            let len = results.len();
             all_results.append(&mut results);
 
+            prev_page = PrevPage {
+                index: prev_page.index + 1,
+                len,
+                total: all_results.len(),
+            };
+
             prev_response = response;
         }
 
+        all_results.truncate(limit);
         Ok(all_results)
     }
 }
@@ -661,18 +2265,326 @@ impl<E> Deref for Paged<E> {
     }
 }
 
-async fn raw_response<C>(response: Response<Body>) -> Result<Response<Vec<u8>>, C::Error>
+#[derive(Clone, Debug)]
+pub struct RetryPolicy {
+    /// total number of attempts made, including the first
+    pub max_attempts: usize,
+    /// backoff before the first retry, doubled after each subsequent attempt
+    pub initial_backoff: Duration,
+    pub max_backoff: Duration,
+    /// when set, each backoff is drawn uniformly from [0, backoff] ("full jitter")
+    pub jitter: bool,
+    /// response statuses which are retried, transport errors (see Client::is_transport_error)
+    /// are always retried and any other error is returned right away
+    pub retry_on: Vec<StatusCode>,
+    /// when set, a Retry-After response header takes precedence over the computed backoff,
+    /// though it's still capped at max_backoff
+    pub honor_retry_after: bool,
+    /// when unset, only requests with idempotent methods are retried
+    pub retry_non_idempotent: bool,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_attempts: 3,
+            initial_backoff: Duration::from_millis(100),
+            max_backoff: Duration::from_secs(10),
+            jitter: true,
+            retry_on: vec![
+                StatusCode::TOO_MANY_REQUESTS,
+                StatusCode::BAD_GATEWAY,
+                StatusCode::SERVICE_UNAVAILABLE,
+                StatusCode::GATEWAY_TIMEOUT,
+            ],
+            honor_retry_after: true,
+            retry_non_idempotent: false,
+        }
+    }
+}
+
+impl RetryPolicy {
+    pub fn max_attempts(max_attempts: usize) -> Self {
+        Self {
+            max_attempts,
+            ..Default::default()
+        }
+    }
+
+    fn is_retryable(&self, method: &Method) -> bool {
+        self.max_attempts > 1 && (self.retry_non_idempotent || is_idempotent(method))
+    }
+
+    fn backoff(&self, attempt: usize, retry_after: Option<Duration>) -> Duration {
+        if let Some(retry_after) = retry_after.filter(|_| self.honor_retry_after) {
+            return retry_after.min(self.max_backoff);
+        }
+        let exponent = u32::try_from(attempt.saturating_sub(1)).unwrap_or(u32::MAX);
+        let backoff = self
+            .initial_backoff
+            .checked_mul(2u32.saturating_pow(exponent))
+            .unwrap_or(self.max_backoff)
+            .min(self.max_backoff);
+        if self.jitter {
+            backoff.mul_f64(random_fraction())
+        } else {
+            backoff
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct Retry<Q> {
+    inner: Q,
+    policy: RetryPolicy,
+}
+
+impl<Q> Retry<Q> {
+    pub fn new(policy: impl Into<Option<RetryPolicy>>, inner: Q) -> Self {
+        Retry {
+            inner,
+            policy: policy.into().unwrap_or_default(),
+        }
+    }
+}
+
+impl<Q> Deref for Retry<Q> {
+    type Target = Q;
+    fn deref(&self) -> &Self::Target {
+        &self.inner
+    }
+}
+
+#[async_trait]
+impl<Q, C, T> Query<C, T> for Retry<Q>
+where
+    Q: Send + Sync + for<'a> Query<RetryClient<'a, C>, T>,
+    C: Client + Debug + Sync,
+{
+    #[framed]
+    #[cfg_attr(feature = "tracing", instrument(err(Debug)))]
+    async fn query(&self, client: &C) -> Result<T, C::Error> {
+        self.inner
+            .query(&RetryClient {
+                client,
+                policy: &self.policy,
+            })
+            .await
+    }
+}
+
+/// RetryClient is the Client a Retry query runs its inner query with, the request body is
+/// buffered so that the request can be rebuilt for each attempt; the request's timeout
+/// applies to each attempt separately, a timed out attempt being retried like a transport error
+#[derive(Debug)]
+pub struct RetryClient<'a, C> {
+    client: &'a C,
+    policy: &'a RetryPolicy,
+}
+
+#[async_trait]
+impl<C: Client + Sync> Client for RetryClient<'_, C> {
+    type Error = C::Error;
+
+    fn headers(&self) -> &HeaderMap {
+        self.client.headers()
+    }
+    fn timeout(&self) -> Option<Duration> {
+        self.client.timeout()
+    }
+    fn max_response_size(&self) -> Option<usize> {
+        self.client.max_response_size()
+    }
+    fn base_uri(&self) -> Option<&str> {
+        Client::base_uri(self.client)
+    }
+    fn auth(&self) -> Option<&dyn AuthProvider> {
+        self.client.auth()
+    }
+    fn is_transport_error(&self, error: &Self::Error) -> bool {
+        self.client.is_transport_error(error)
+    }
+    fn applies_timeout(&self) -> bool {
+        true
+    }
+    async fn rest(&self, request: Request<Body>) -> Result<Response<Body>, Self::Error> {
+        let timeout = request.extensions().get::<RequestTimeout>().copied();
+        if !self.policy.is_retryable(request.method()) {
+            return self.attempt(request, timeout).await;
+        }
+        let endpoint = request.extensions().get::<EndpointName>().copied();
+
+        let (parts, body) = request.into_parts();
+        let body = to_bytes(body)
+            .await
+            .map_err(|err| BaseClientError::RequestBodyBuild(format!("{err}")))?;
+
+        let mut attempt = 1;
+        loop {
+            let mut request = Request::builder()
+                .method(parts.method.clone())
+                .uri(parts.uri.clone())
+                .version(parts.version)
+                .body(Body::from(body.clone()))
+                .map_err(|err| BaseClientError::RequestBodyBuild(format!("{err}")))?;
+            *request.headers_mut() = parts.headers.clone();
+            if let Some(endpoint) = endpoint {
+                request.extensions_mut().insert(endpoint);
+            }
+
+            let result = self.attempt(request, timeout).await;
+            let retry_after = match &result {
+                Ok(response) if self.policy.retry_on.contains(&response.status()) => retry_after(response.headers()),
+                Ok(response) => {
+                    tracing::debug!(attempt, status = %response.status(), "request attempt succeeded");
+                    return result;
+                }
+                Err(err) if self.client.is_transport_error(err) => None,
+                Err(_) => return result,
+            };
+
+            if attempt >= self.policy.max_attempts {
+                match &result {
+                    Ok(response) => tracing::warn!(attempt, status = %response.status(), "request attempts exhausted"),
+                    Err(err) => tracing::warn!(attempt, error = %err, "request attempts exhausted"),
+                };
+                return result;
+            }
+
+            let backoff = self.policy.backoff(attempt, retry_after);
+            match &result {
+                Ok(response) => {
+                    tracing::info!(attempt, status = %response.status(), ?backoff, "request attempt failed, retrying")
+                }
+                Err(err) => tracing::info!(attempt, error = %err, ?backoff, "request attempt failed, retrying"),
+            };
+            tokio::time::sleep(backoff).await;
+            attempt += 1;
+        }
+    }
+}
+
+impl<C: Client + Sync> RetryClient<'_, C> {
+    /// sends a single attempt bound by the request's timeout
+    async fn attempt(
+        &self,
+        request: Request<Body>,
+        timeout: Option<RequestTimeout>,
+    ) -> Result<Response<Body>, C::Error> {
+        let Some(RequestTimeout(timeout)) = timeout else {
+            return self.client.rest(request).await;
+        };
+        let deadline = Instant::now() + timeout;
+        let mut response = timeout_at(deadline, self.client.rest(request))
+            .await
+            .map_err(|_| BaseClientError::Timeout)??;
+        response.extensions_mut().insert(ResponseDeadline(deadline));
+        Ok(response)
+    }
+}
+
+fn is_idempotent(method: &Method) -> bool {
+    matches!(
+        *method,
+        Method::GET | Method::HEAD | Method::OPTIONS | Method::TRACE | Method::PUT | Method::DELETE
+    )
+}
+
+/// parses a Retry-After header given either as delay-seconds or as an HTTP-date
+fn retry_after(headers: &HeaderMap) -> Option<Duration> {
+    let retry_after = headers.get(RETRY_AFTER)?.to_str().ok()?.trim();
+    if let Ok(seconds) = retry_after.parse::<u64>() {
+        return Some(Duration::from_secs(seconds));
+    }
+    let date = chrono::DateTime::parse_from_rfc2822(retry_after).ok()?;
+    (date.with_timezone(&chrono::Utc) - chrono::Utc::now()).to_std().ok()
+}
+
+thread_local! {
+    static RANDOM_STATE: Cell<u64> = Cell::new(RandomState::new().build_hasher().finish() | 1);
+}
+
+/// xorshift64* seeded once per thread from the randomly seeded std hasher, avoids a dependency on rand
+fn random_u64() -> u64 {
+    RANDOM_STATE.with(|state| {
+        let mut x = state.get();
+        x ^= x >> 12;
+        x ^= x << 25;
+        x ^= x >> 27;
+        state.set(x);
+        x.wrapping_mul(0x2545_F491_4F6C_DD1D)
+    })
+}
+
+/// a value in [0, 1) used for jitter
+fn random_fraction() -> f64 {
+    (random_u64() >> 11) as f64 / (1u64 << 53) as f64
+}
+
+/// sends the request bound by the deadline, unless the client applies the request's timeout itself
+async fn rest<C>(client: &C, request: Request<Body>, deadline: Option<Instant>) -> Result<Response<Body>, C::Error>
+where
+    C: Client,
+{
+    match deadline.filter(|_| !client.applies_timeout()) {
+        Some(deadline) => timeout_at(deadline, client.rest(request))
+            .await
+            .map_err(|_| BaseClientError::Timeout)?,
+        None => client.rest(request).await,
+    }
+}
+
+pub(crate) async fn raw_response<C>(
+    response: Response<Body>,
+    limits: ResponseLimits,
+) -> Result<Response<Vec<u8>>, C::Error>
 where
     C: Client,
 {
     let (parts, body) = response.into_parts();
-    let bytes = to_bytes(body)
-        .await
-        .map(|bytes| bytes.to_vec())
-        .map_err(BaseClientError::ResponseBodyInvalidCharacter)?;
+    let deadline = parts
+        .extensions
+        .get::<ResponseDeadline>()
+        .map(|deadline| deadline.0)
+        .or(limits.deadline);
+    let bytes = match deadline {
+        Some(deadline) => timeout_at(deadline, read_body(body, limits.max_response_size))
+            .await
+            .map_err(|_| BaseClientError::Timeout)?,
+        None => read_body(body, limits.max_response_size).await,
+    }?;
     Ok(Response::from_parts(parts, bytes))
 }
 
+/// reads the body chunk by chunk so that a body exceeding max_size is aborted
+/// without being buffered in full
+pub(crate) async fn read_body(mut body: Body, max_size: Option<usize>) -> Result<Vec<u8>, BaseClientError> {
+    let max_size = match max_size {
+        Some(max_size) => max_size,
+        None => {
+            return to_bytes(body)
+                .await
+                .map(|bytes| bytes.to_vec())
+                .map_err(BaseClientError::ResponseBodyInvalidCharacter)
+        }
+    };
+
+    let size_hint = usize::try_from(HttpBody::size_hint(&body).lower()).unwrap_or(usize::MAX);
+    if size_hint > max_size {
+        return Err(BaseClientError::BodyTooLarge);
+    }
+
+    let mut bytes = Vec::with_capacity(size_hint);
+    while let Some(chunk) = body.data().await {
+        let chunk = chunk.map_err(BaseClientError::ResponseBodyInvalidCharacter)?;
+        if bytes.len() + chunk.len() > max_size {
+            return Err(BaseClientError::BodyTooLarge);
+        }
+        bytes.extend_from_slice(&chunk);
+    }
+    Ok(bytes)
+}
+
 pub trait UnwrapResponse<T> {
     fn unwrap_response(self) -> T;
 }
@@ -707,19 +2619,24 @@ where
     #[framed]
     #[cfg_attr(feature = "tracing", instrument(err(Debug)))]
     async fn query(&self, client: &C) -> Result<(), C::Error> {
-        let uri = self.uri(client)?;
-        let headers = self.headers();
-
-        let request = self.request(client, &uri, &headers, None)?;
+        let target = self.target(client)?;
+        let headers = self.try_headers()?;
 
-        let response = client.rest(request).await?;
+        let limits = self.limits(client);
+        let response = self.send(client, &target, &headers, None, limits).await?;
 
         let status = response.status();
         if status == StatusCode::NOT_FOUND {
             return Ok(());
         }
         if status.is_client_error() || status.is_server_error() {
-            return Err(C::Error::from(raw_response::<C>(response).await?));
+            return Err(C::Error::from(
+                self.error_response(raw_response::<C>(response, limits).await?),
+            ));
+        }
+        // a 304 Not Modified is a hit, an ignored response has nothing to decode
+        if status.is_redirection() && status != StatusCode::NOT_MODIFIED {
+            return Err(redirection_error::<C>(raw_response::<C>(response, limits).await?));
         }
 
         Ok(())
@@ -735,21 +2652,489 @@ where
     #[framed]
     #[cfg_attr(feature = "tracing", instrument(err(Debug)))]
     async fn query(&self, client: &C) -> Result<(), C::Error> {
-        let uri = self.uri(client)?;
-        let headers = self.headers();
-
-        let request = self.request(client, &uri, &headers, None)?;
+        let target = self.target(client)?;
+        let headers = self.try_headers()?;
 
-        let response = client.rest(request).await?;
+        let limits = self.limits(client);
+        let response = self.send(client, &target, &headers, None, limits).await?;
 
         let status = response.status();
         if status == StatusCode::NOT_FOUND {
             return Ok(());
         }
         if status.is_client_error() || status.is_server_error() {
-            return Err(C::Error::from(raw_response::<C>(response).await?));
+            return Err(C::Error::from(
+                self.error_response(raw_response::<C>(response, limits).await?),
+            ));
+        }
+        // a 304 Not Modified is a hit, an ignored response has nothing to decode
+        if status.is_redirection() && status != StatusCode::NOT_MODIFIED {
+            return Err(redirection_error::<C>(raw_response::<C>(response, limits).await?));
         }
 
         Ok(())
     }
 }
+
+#[cfg(all(test, feature = "mock"))]
+mod tests {
+    use super::*;
+    use crate::{Expectation, MockClient};
+    use std::collections::VecDeque;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::{Arc, Mutex};
+
+    /// answers each request with the next scripted outcome, after the next scripted delay if any
+    #[derive(Debug, Default)]
+    struct ScriptedClient {
+        outcomes: Mutex<VecDeque<Result<StatusCode, BaseClientError>>>,
+        delays: Mutex<VecDeque<Duration>>,
+        requests: Mutex<usize>,
+    }
+
+    impl ScriptedClient {
+        fn new(outcomes: impl IntoIterator<Item = Result<StatusCode, BaseClientError>>) -> Self {
+            Self {
+                outcomes: Mutex::new(outcomes.into_iter().collect()),
+                ..Default::default()
+            }
+        }
+
+        fn with_delays(self, delays: impl IntoIterator<Item = Duration>) -> Self {
+            *self.delays.lock().unwrap() = delays.into_iter().collect();
+            self
+        }
+
+        fn requests(&self) -> usize {
+            *self.requests.lock().unwrap()
+        }
+    }
+
+    #[async_trait]
+    impl Client for ScriptedClient {
+        type Error = BaseClientError;
+
+        fn headers(&self) -> &HeaderMap {
+            &EMPTY_HEADER_MAP
+        }
+        fn is_transport_error(&self, error: &Self::Error) -> bool {
+            error.is_transport()
+        }
+        async fn rest(&self, _: Request<Body>) -> Result<Response<Body>, Self::Error> {
+            *self.requests.lock().unwrap() += 1;
+            let delay = self.delays.lock().unwrap().pop_front();
+            if let Some(delay) = delay {
+                tokio::time::sleep(delay).await;
+            }
+            let status = self.outcomes.lock().unwrap().pop_front().expect("no outcome left")?;
+            Ok(Response::builder().status(status).body(Body::empty()).unwrap())
+        }
+    }
+
+    #[derive(Debug)]
+    struct GetItems;
+
+    impl Endpoint for GetItems {
+        const METHOD: Method = Method::GET;
+        type Params<'a> = ();
+        type Body<'a> = ();
+        type Response<T> = DefaultResponse<T>;
+        type ErrorBody = ProblemDetails;
+
+        fn path(&self) -> Path {
+            "http://localhost/items".into()
+        }
+        fn params(&self) -> Self::Params<'_> {}
+        fn timeout(&self) -> Option<Duration> {
+            Some(Duration::from_secs(1))
+        }
+    }
+
+    fn policy() -> RetryPolicy {
+        RetryPolicy {
+            jitter: false,
+            ..Default::default()
+        }
+    }
+
+    async fn send<C: Client + Sync>(
+        client: &C,
+        policy: &RetryPolicy,
+        method: Method,
+    ) -> Result<Response<Body>, C::Error> {
+        let request = Request::builder()
+            .method(method)
+            .uri("http://localhost/items")
+            .body(Body::empty())
+            .unwrap();
+        RetryClient { client, policy }.rest(request).await
+    }
+
+    #[test]
+    fn test_backoff_doubles_up_to_max_backoff() {
+        let policy = RetryPolicy {
+            max_backoff: Duration::from_millis(350),
+            ..policy()
+        };
+        let backoffs: Vec<_> = (1..=4).map(|attempt| policy.backoff(attempt, None)).collect();
+        assert_eq!(backoffs, [100, 200, 350, 350].map(Duration::from_millis).to_vec(),);
+
+        let jittered = RetryPolicy {
+            jitter: true,
+            ..policy.clone()
+        };
+        for attempt in 1..=4 {
+            assert!(jittered.backoff(attempt, None) <= policy.backoff(attempt, None));
+        }
+    }
+
+    #[test]
+    fn test_retry_after_is_capped_at_max_backoff() {
+        let policy = policy();
+        assert_eq!(policy.backoff(1, Some(Duration::from_secs(2))), Duration::from_secs(2));
+        assert_eq!(policy.backoff(1, Some(Duration::from_secs(3600))), policy.max_backoff);
+
+        let ignored = RetryPolicy {
+            honor_retry_after: false,
+            ..policy
+        };
+        assert_eq!(
+            ignored.backoff(1, Some(Duration::from_secs(2))),
+            Duration::from_millis(100)
+        );
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_retryable_status_is_retried_honoring_retry_after() {
+        let client = MockClient::new();
+        client
+            .expect(
+                Expectation::new(Method::GET, "/items")
+                    .times(2)
+                    .respond_status(StatusCode::SERVICE_UNAVAILABLE)
+                    .respond_header(RETRY_AFTER, HeaderValue::from_static("5")),
+            )
+            .expect(Expectation::new(Method::GET, "/items").times(1));
+
+        let start = Instant::now();
+        let response = send(&client, &policy(), Method::GET).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(start.elapsed(), Duration::from_secs(10));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_non_idempotent_request_is_not_retried() {
+        let client = MockClient::new();
+        client.expect(
+            Expectation::new(Method::POST, "/items")
+                .times(1)
+                .respond_status(StatusCode::SERVICE_UNAVAILABLE),
+        );
+
+        let response = send(&client, &policy(), Method::POST).await.unwrap();
+        assert_eq!(response.status(), StatusCode::SERVICE_UNAVAILABLE);
+
+        let client = ScriptedClient::new([Err(BaseClientError::Timeout), Ok(StatusCode::OK)]);
+        let policy = RetryPolicy {
+            retry_non_idempotent: true,
+            ..policy()
+        };
+        assert!(send(&client, &policy, Method::POST).await.is_ok());
+        assert_eq!(client.requests(), 2);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_only_transport_errors_are_retried() {
+        let client = ScriptedClient::new([
+            Err(BaseClientError::Timeout),
+            Err(BaseClientError::Timeout),
+            Ok(StatusCode::OK),
+        ]);
+        assert!(send(&client, &policy(), Method::GET).await.is_ok());
+        assert_eq!(client.requests(), 3);
+
+        let client = ScriptedClient::new([Err(BaseClientError::Auth("expired".into())), Ok(StatusCode::OK)]);
+        assert!(matches!(
+            send(&client, &policy(), Method::GET).await,
+            Err(BaseClientError::Auth(_))
+        ));
+        assert_eq!(client.requests(), 1);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_attempts_are_exhausted() {
+        let client = ScriptedClient::new([
+            Ok(StatusCode::BAD_GATEWAY),
+            Err(BaseClientError::Timeout),
+            Ok(StatusCode::BAD_GATEWAY),
+        ]);
+        let response = send(&client, &policy(), Method::GET).await.unwrap();
+        assert_eq!(response.status(), StatusCode::BAD_GATEWAY);
+        assert_eq!(client.requests(), 3);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_timeout_applies_to_each_attempt() {
+        let client = ScriptedClient::new([Ok(StatusCode::OK), Ok(StatusCode::OK)])
+            .with_delays([Duration::from_secs(2), Duration::from_millis(800)]);
+
+        let start = Instant::now();
+        GetItems.ignore().retry(policy()).query(&client).await.unwrap();
+        assert_eq!(client.requests(), 2);
+        assert_eq!(start.elapsed(), Duration::from_millis(1900));
+
+        let client = ScriptedClient::new([Ok(StatusCode::OK)]).with_delays([Duration::from_secs(2)]);
+        assert!(matches!(
+            GetItems.ignore().query(&client).await,
+            Err(BaseClientError::Timeout)
+        ));
+    }
+
+    /// serializes as bytes like serde_bytes::Bytes
+    #[derive(Debug)]
+    struct Bytes(&'static [u8]);
+
+    impl Serialize for Bytes {
+        fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+            serializer.serialize_bytes(self.0)
+        }
+    }
+
+    #[test]
+    fn test_raw_encoding_requires_string_or_bytes() {
+        let (body, content_type) = BodyEncoding::Raw.encode(&Bytes(b"\x01\x02")).unwrap();
+        assert_eq!(
+            (body.as_slice(), content_type.to_str().unwrap()),
+            (&[1, 2][..], "application/octet-stream")
+        );
+
+        let (body, content_type) = BodyEncoding::Raw.encode(&"text").unwrap();
+        assert_eq!(
+            (body.as_slice(), content_type.to_str().unwrap()),
+            (&b"text"[..], "text/plain; charset=utf-8")
+        );
+
+        assert!(BodyEncoding::Raw.encode(&vec![1u8, 2]).is_err());
+        assert!(BodyEncoding::Raw.encode(&Vec::<u8>::new()).is_err());
+    }
+
+    #[test]
+    fn test_multipart_encoding_only_sends_bytes_as_octet_stream() {
+        #[derive(Serialize)]
+        struct Upload {
+            name: &'static str,
+            size: u32,
+            file: Bytes,
+            empty: Bytes,
+            checksum: Vec<u8>,
+            comment: Option<String>,
+        }
+
+        let (body, content_type) = BodyEncoding::Multipart
+            .encode(&Upload {
+                name: "a.bin",
+                size: 2,
+                file: Bytes(b"\x01\x02"),
+                empty: Bytes(b""),
+                checksum: vec![],
+                comment: None,
+            })
+            .unwrap();
+        let boundary = content_type
+            .to_str()
+            .unwrap()
+            .strip_prefix("multipart/form-data; boundary=")
+            .unwrap()
+            .to_owned();
+        let part = |name: &str, content_type: &str, content: &[u8]| {
+            let mut part = format!(
+                "--{boundary}\r\ncontent-disposition: form-data; name=\"{name}\"\r\ncontent-type: {content_type}\r\n\r\n"
+            )
+            .into_bytes();
+            part.extend_from_slice(content);
+            part.extend_from_slice(b"\r\n");
+            part
+        };
+        let expected = [
+            part("name", "text/plain; charset=utf-8", b"a.bin"),
+            part("size", "text/plain; charset=utf-8", b"2"),
+            part("file", "application/octet-stream", b"\x01\x02"),
+            part("empty", "application/octet-stream", b""),
+            part("checksum", "application/json", b"[]"),
+            format!("--{boundary}--\r\n").into_bytes(),
+        ]
+        .concat();
+        assert_eq!(body, expected);
+
+        assert!(BodyEncoding::Multipart.encode(&"text").is_err());
+    }
+
+    #[derive(Debug)]
+    struct ListItems;
+
+    impl Endpoint for ListItems {
+        const METHOD: Method = Method::GET;
+        type Params<'a> = ();
+        type Body<'a> = ();
+        type Response<T> = DefaultResponse<T>;
+        type ErrorBody = ProblemDetails;
+
+        fn path(&self) -> Path {
+            "http://localhost/api/items".into()
+        }
+        fn params(&self) -> Self::Params<'_> {}
+    }
+
+    impl Pageable for ListItems {
+        fn next_page(prev_response: &Response<Vec<u8>>, _: &PrevPage) -> Option<NextPage> {
+            NextPage::link_header(prev_response)
+        }
+    }
+
+    /// serves the first `pages` of the pages [1, 2], [3, 4], [5] linked with relative links
+    fn paged_client(pages: usize) -> MockClient {
+        let page = |query: &'static str, link: Option<&'static str>, items: &'static str| {
+            let expectation = Expectation::new(Method::GET, "/api/items").params(&query).times(1);
+            match link {
+                Some(link) => expectation.respond_header(LINK, HeaderValue::from_static(link)),
+                None => expectation,
+            }
+            .respond_body(items)
+        };
+        let client = MockClient::new();
+        for page in [
+            page("", Some("</api/items?page=2>; rel=\"next\""), "[1, 2]"),
+            page("page=2", Some("<items?page=3>; rel=\"next\""), "[3, 4]"),
+            page("page=3", None, "[5]"),
+        ]
+        .into_iter()
+        .take(pages)
+        {
+            client.expect(page);
+        }
+        client
+    }
+
+    #[tokio::test]
+    async fn test_relative_links_are_followed() {
+        let items: Vec<u32> = ListItems
+            .paginated(Pagination::All)
+            .query(&paged_client(3))
+            .await
+            .unwrap();
+        assert_eq!(items, [1, 2, 3, 4, 5]);
+    }
+
+    #[tokio::test]
+    async fn test_limit_truncates_results() {
+        // the third page is never requested
+        let client = paged_client(2);
+        let items: Vec<u32> = ListItems.paginated(Pagination::Limit(3)).query(&client).await.unwrap();
+        assert_eq!(items, [1, 2, 3]);
+    }
+
+    #[derive(Debug)]
+    struct GetUser {
+        rendered: Arc<AtomicUsize>,
+    }
+
+    impl Endpoint for GetUser {
+        const METHOD: Method = Method::GET;
+        type Params<'a> = ();
+        type Body<'a> = ();
+        type Response<T> = DefaultResponse<T>;
+        type ErrorBody = ProblemDetails;
+
+        fn path(&self) -> Path {
+            self.rendered.fetch_add(1, Ordering::Relaxed);
+            path!("http://localhost/users/{id}?view=full", id = "a/b")
+        }
+        fn params(&self) -> Self::Params<'_> {}
+    }
+
+    #[tokio::test]
+    async fn test_path_is_rendered_once_per_query() {
+        let client = MockClient::new();
+        client.expect(Expectation::new(Method::GET, "/users/a%2Fb").params(&serde_json::json!({ "view": "full" })));
+        let rendered = Arc::new(AtomicUsize::new(0));
+        GetUser {
+            rendered: rendered.clone(),
+        }
+        .ignore()
+        .query(&client)
+        .await
+        .unwrap();
+        assert_eq!(rendered.load(Ordering::Relaxed), 1);
+    }
+
+    #[test]
+    fn test_route_falls_back_to_path_template() {
+        let client = MockClient::new();
+        let endpoint = GetUser {
+            rendered: Arc::default(),
+        };
+        assert_eq!(endpoint.target(&client).unwrap().route, "http://localhost/users/{id}");
+        assert_eq!(GetItems.target(&client).unwrap().route, "http://localhost/items");
+        assert_eq!(Path::from("/items?page=1").template(), Some("/items"));
+    }
+
+    #[test]
+    fn test_resolve_reference() {
+        let base: Uri = "http://localhost/a/b/c?q=1".parse().unwrap();
+        let resolve = |reference| resolve_reference(&base, reference).unwrap().to_string();
+        assert_eq!(resolve("https://example.com/x"), "https://example.com/x");
+        assert_eq!(resolve("//example.com/x"), "http://example.com/x");
+        assert_eq!(resolve("/x?page=2"), "http://localhost/x?page=2");
+        assert_eq!(resolve("?page=2"), "http://localhost/a/b/c?page=2");
+        assert_eq!(resolve("d?page=2#fragment"), "http://localhost/a/b/d?page=2");
+        assert_eq!(resolve("../d"), "http://localhost/a/d");
+        assert_eq!(resolve("./"), "http://localhost/a/b/");
+        assert_eq!(resolve(""), "http://localhost/a/b/c?q=1");
+    }
+
+    /// delays each request by the given duration
+    #[derive(Debug)]
+    struct Delay(Duration);
+
+    #[async_trait]
+    impl<C: Client + Sync> ClientMiddleware<C> for Delay {
+        async fn handle(&self, request: Request<Body>, client: &C) -> Result<Response<Body>, C::Error> {
+            tokio::time::sleep(self.0).await;
+            client.rest(request).await
+        }
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_stream_prefetches_next_page() {
+        for (prefetch, elapsed) in [(false, 7), (true, 6)] {
+            let client = paged_client(2).with_middleware(Delay(Duration::from_secs(1)));
+            let paged = ListItems.paginated(Pagination::Limit(3));
+            let paged = if prefetch { paged.prefetch() } else { paged };
+
+            let start = Instant::now();
+            let mut stream = pin!(paged.stream::<_, u32>(&client));
+            assert_eq!(stream.next().await.unwrap().unwrap(), 1);
+            // the next page is requested while the first item is being processed
+            tokio::time::sleep(Duration::from_secs(5)).await;
+            let items: Vec<u32> = stream.map(Result::unwrap).collect().await;
+            assert_eq!(items, [2, 3]);
+            assert_eq!(start.elapsed(), Duration::from_secs(elapsed), "prefetch: {prefetch}");
+        }
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_query_all_fails_fast() {
+        let client = ScriptedClient::new([Err(BaseClientError::Auth("expired".into())), Ok(StatusCode::OK)])
+            .with_delays([Duration::from_millis(900), Duration::ZERO]);
+
+        let start = Instant::now();
+        let result = query_all(&client, [GetItems.ignore(), GetItems.ignore()], 2).await;
+        assert!(matches!(result, Err(BaseClientError::Auth(_))));
+        assert_eq!(start.elapsed(), Duration::ZERO);
+
+        let client = ScriptedClient::new([Ok(StatusCode::OK), Err(BaseClientError::Auth("expired".into()))])
+            .with_delays([Duration::from_millis(900), Duration::ZERO]);
+        let results = query_all_settled(&client, [GetItems.ignore(), GetItems.ignore()], 2).await;
+        assert!(matches!(results[..], [Err(BaseClientError::Auth(_)), Ok(())]));
+    }
+}
@@ -247,22 +247,41 @@ impl From<BaseClientError> for Error {
     #[framed]
     fn from(base_client_error: BaseClientError) -> Self {
         match base_client_error {
+            BaseClientError::Auth(err) => Self::default_details(err),
             BaseClientError::BodyTooLarge => Self::default(),
+            BaseClientError::CircuitOpen => Self::new(StatusCode::SERVICE_UNAVAILABLE),
+            BaseClientError::GraphQL(errors) => match errors.iter().find_map(|error| Some((error.status()?, error))) {
+                Some((status, error)) => Self::msg(status, &error.message),
+                None => Self::default_details(
+                    errors
+                        .iter()
+                        .map(|error| error.message.as_str())
+                        .collect::<Vec<_>>()
+                        .join("; "),
+                ),
+            },
+            BaseClientError::InvalidHeader(err) => Self::default_details(err),
             BaseClientError::InvalidUri(invalid_uri) => Self::default_details(invalid_uri),
             BaseClientError::NetworkError(err) => Self::default_details(err),
+            BaseClientError::NotModified { .. } => Self::new(StatusCode::NOT_MODIFIED),
             BaseClientError::RequestBodyBuild(err) => Self::default_details(err),
             BaseClientError::RequestBodySerialization(err) => Self::default_details(err),
+            BaseClientError::RateLimited { .. } => Self::new(StatusCode::TOO_MANY_REQUESTS),
             BaseClientError::RequestParamsSerialization(err) => Self::default_details(err),
-            BaseClientError::Response { status, message } => Self::details(status, message),
+            BaseClientError::Response { status, message, .. } => Self::details(status, message),
+            BaseClientError::ResponseBodyDecode(err) => Self::default_details(err),
             BaseClientError::ResponseBodyDeserialization(err) => Self::default_details(err),
             BaseClientError::ResponseBodyInvalidCharacter(err) => Self::default_details(err),
+            BaseClientError::Service(err) => Self::default_details(err),
+            BaseClientError::Timeout => Self::new(StatusCode::GATEWAY_TIMEOUT),
+            BaseClientError::Tls(err) => Self::default_details(err),
         }
     }
 }
 
 #[cfg(feature = "client")]
-impl<Body> From<hyper::Response<Body>> for Error {
-    default fn from(response: hyper::Response<Body>) -> Self {
+impl From<hyper::Response<hyper::Body>> for Error {
+    fn from(response: hyper::Response<hyper::Body>) -> Self {
         let status = response.status();
         if !(status.is_client_error() || status.is_server_error()) {
             unimplemented!();
@@ -0,0 +1,369 @@
+use crate::{raw_response, BaseClientError, Client, Decoder, DefaultResponse, Endpoint, EndpointRequest, Path, Query};
+use async_trait::async_trait;
+use hyper::{Method, StatusCode};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use std::fmt::Debug;
+
+#[cfg(feature = "tracing")]
+use tracing::instrument;
+
+/// GraphQLQuery is sent as a POST request with a `{query, variables, operationName}` JSON body
+/// to `/graphql` relative to the client's base uri, querying it decodes the response's `data` into
+/// the requested type or fails with BaseClientError::GraphQL if the response carries `errors`,
+/// also if it's an error response, in which case errors without a `status` extension are given
+/// the response's status; e.g.
+/// `GraphQLQuery::new("query User($id: ID!) { user(id: $id) { name } }").variables(json!({ "id": id }))`
+///
+/// Persisted queries are sent as their SHA-256 hash in the `persistedQuery` extension, if the
+/// server doesn't know the hash yet and the query's text is known the query is sent again with
+/// its text so that the server can register it
+#[derive(Clone, Debug)]
+pub struct GraphQLQuery<V = ()> {
+    path: Path,
+    query: Option<String>,
+    variables: Option<V>,
+    operation_name: Option<String>,
+    sha256_hash: Option<String>,
+}
+
+impl GraphQLQuery {
+    pub fn new(query: impl Into<String>) -> Self {
+        Self {
+            path: Path::from("/graphql"),
+            query: Some(query.into()),
+            variables: None,
+            operation_name: None,
+            sha256_hash: None,
+        }
+    }
+
+    /// a query the server already knows by its hex encoded SHA-256 hash, e.g. from a
+    /// persisted query manifest
+    pub fn persisted(sha256_hash: impl Into<String>) -> Self {
+        Self {
+            path: Path::from("/graphql"),
+            query: None,
+            variables: None,
+            operation_name: None,
+            sha256_hash: Some(sha256_hash.into()),
+        }
+    }
+}
+
+impl<V> GraphQLQuery<V> {
+    pub fn variables<W: Serialize>(self, variables: W) -> GraphQLQuery<W> {
+        GraphQLQuery {
+            path: self.path,
+            query: self.query,
+            variables: Some(variables),
+            operation_name: self.operation_name,
+            sha256_hash: self.sha256_hash,
+        }
+    }
+
+    pub fn operation_name(self, operation_name: impl Into<String>) -> Self {
+        Self {
+            operation_name: Some(operation_name.into()),
+            ..self
+        }
+    }
+
+    /// sends the query as a persisted query, i.e. by the SHA-256 hash of its text
+    pub fn persist(self) -> Self {
+        let sha256_hash = self.query.as_deref().map(|query| {
+            data_encoding::HEXLOWER.encode(ring::digest::digest(&ring::digest::SHA256, query.as_bytes()).as_ref())
+        });
+        Self {
+            sha256_hash: sha256_hash.or(self.sha256_hash),
+            ..self
+        }
+    }
+
+    /// path of the GraphQL endpoint, defaults to `/graphql`
+    pub fn path(self, path: impl Into<Path>) -> Self {
+        Self {
+            path: path.into(),
+            ..self
+        }
+    }
+}
+
+/// a single entry of a GraphQL response's `errors`
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct GraphQLError {
+    pub message: String,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub locations: Vec<GraphQLErrorLocation>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub path: Vec<serde_json::Value>,
+    #[serde(default, skip_serializing_if = "serde_json::Map::is_empty")]
+    pub extensions: serde_json::Map<String, serde_json::Value>,
+}
+
+#[derive(Clone, Copy, Debug, Deserialize, Serialize)]
+pub struct GraphQLErrorLocation {
+    pub line: usize,
+    pub column: usize,
+}
+
+impl GraphQLError {
+    /// the status set in the error's extensions as done by Error::graphql, converting
+    /// BaseClientError::GraphQL into an Error uses the status of the first error which has one
+    pub fn status(&self) -> Option<StatusCode> {
+        let status = self.extensions.get("status")?.as_u64()?;
+        StatusCode::from_u16(u16::try_from(status).ok()?).ok()
+    }
+
+    pub fn code(&self) -> Option<&str> {
+        self.extensions.get("code")?.as_str()
+    }
+
+    fn is_persisted_query_not_found(&self) -> bool {
+        self.code() == Some("PERSISTED_QUERY_NOT_FOUND") || self.message == "PersistedQueryNotFound"
+    }
+
+    fn with_default_status(mut self, status: StatusCode) -> Self {
+        self.extensions
+            .entry("status")
+            .or_insert_with(|| status.as_u16().into());
+        self
+    }
+}
+
+/// the errors of an error response, available through BaseClientError::error_body
+#[derive(Clone, Debug, Deserialize)]
+pub struct GraphQLErrors {
+    #[serde(default)]
+    pub errors: Vec<GraphQLError>,
+}
+
+#[derive(Debug, Deserialize)]
+struct GraphQLResponse<T> {
+    #[serde(default = "Option::default")]
+    data: Option<T>,
+    #[serde(default)]
+    errors: Vec<GraphQLError>,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct GraphQLRequestBody<'a, V> {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    query: Option<&'a str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    variables: Option<&'a V>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    operation_name: Option<&'a str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    extensions: Option<GraphQLRequestExtensions<'a>>,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct GraphQLRequestExtensions<'a> {
+    persisted_query: PersistedQuery<'a>,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct PersistedQuery<'a> {
+    version: u8,
+    sha256_hash: &'a str,
+}
+
+/// a single request for a GraphQLQuery, with or without the query's text
+#[derive(Debug)]
+struct GraphQLRequest<'a, V> {
+    query: &'a GraphQLQuery<V>,
+    include_query: bool,
+}
+
+impl<V: Debug + Serialize + Send + Sync> Endpoint for GraphQLRequest<'_, V> {
+    const METHOD: Method = Method::POST;
+
+    type Params<'a>
+        = ()
+    where
+        Self: 'a;
+    type Body<'a>
+        = GraphQLRequestBody<'a, V>
+    where
+        Self: 'a;
+    type Response<T> = DefaultResponse<T>;
+    type ErrorBody = GraphQLErrors;
+
+    fn path(&self) -> Path {
+        self.query.path.clone()
+    }
+    fn params(&self) -> Self::Params<'_> {}
+    fn body(&self) -> Option<Self::Body<'_>> {
+        let GraphQLQuery {
+            query,
+            variables,
+            operation_name,
+            sha256_hash,
+            ..
+        } = self.query;
+        Some(GraphQLRequestBody {
+            query: query.as_deref().filter(|_| self.include_query),
+            variables: variables.as_ref(),
+            operation_name: operation_name.as_deref(),
+            extensions: sha256_hash.as_deref().map(|sha256_hash| GraphQLRequestExtensions {
+                persisted_query: PersistedQuery {
+                    version: 1,
+                    sha256_hash,
+                },
+            }),
+        })
+    }
+}
+
+#[async_trait]
+impl<C, T, V> Query<C, T> for GraphQLQuery<V>
+where
+    C: Client + Debug + Sync,
+    T: Debug + DeserializeOwned + Send,
+    V: Debug + Serialize + Send + Sync,
+{
+    #[framed]
+    #[cfg_attr(feature = "tracing", instrument(err(Debug)))]
+    async fn query(&self, client: &C) -> Result<T, C::Error> {
+        let include_query = self.sha256_hash.is_none();
+        let mut response: GraphQLResponse<T> = self.send(client, include_query).await?;
+
+        let unknown_hash = response.errors.iter().any(GraphQLError::is_persisted_query_not_found);
+        if unknown_hash && !include_query && self.query.is_some() {
+            tracing::debug!("persisted query not found, sending query text");
+            response = self.send(client, true).await?;
+        }
+
+        if !response.errors.is_empty() {
+            return Err(C::Error::from(BaseClientError::GraphQL(response.errors)));
+        }
+        match response.data {
+            Some(data) => Ok(data),
+            None => serde_json::from_value(serde_json::Value::Null)
+                .map_err(|err| C::Error::from(BaseClientError::ResponseBodyDeserialization(err))),
+        }
+    }
+}
+
+impl<V: Debug + Serialize + Send + Sync> GraphQLQuery<V> {
+    /// sends a single request, the errors of an error response are returned like those
+    /// of a successful response
+    async fn send<C, T>(&self, client: &C, include_query: bool) -> Result<GraphQLResponse<T>, C::Error>
+    where
+        C: Client,
+        T: DeserializeOwned,
+    {
+        let request = GraphQLRequest {
+            query: self,
+            include_query,
+        };
+        let target = request.target(client)?;
+        let headers = request.try_headers()?;
+
+        let limits = request.limits(client);
+        let response = request.send(client, &target, &headers, None, limits).await?;
+
+        let status = response.status();
+        let response = raw_response::<C>(response, limits).await?;
+        if !(status.is_client_error() || status.is_server_error()) {
+            return Decoder::Json.decode(&response).map_err(C::Error::from);
+        }
+        match Decoder::ContentType.decode::<GraphQLErrors>(&response) {
+            Ok(GraphQLErrors { errors }) if !errors.is_empty() => Ok(GraphQLResponse {
+                data: None,
+                errors: errors
+                    .into_iter()
+                    .map(|error| error.with_default_status(status))
+                    .collect(),
+            }),
+            _ => Err(C::Error::from(request.error_response(response))),
+        }
+    }
+}
+
+#[cfg(all(test, feature = "mock"))]
+mod tests {
+    use super::*;
+    use crate::{Expectation, MockClient};
+    use serde_json::json;
+
+    const SHA256_HASH: &str = "1c7e1e347f726166b5b1c55afd61f278cc9b45e00c108ec33d540a566379811b";
+
+    #[derive(Debug, Deserialize, PartialEq)]
+    struct Data {
+        a: u32,
+    }
+
+    #[tokio::test]
+    async fn test_unknown_persisted_query_is_resent_with_its_text() {
+        let extensions = json!({ "persistedQuery": { "version": 1, "sha256Hash": SHA256_HASH } });
+        let client = MockClient::new();
+        client
+            .expect(
+                Expectation::new(Method::POST, "/graphql")
+                    .json_body(&json!({ "extensions": extensions }))
+                    .times(1)
+                    .respond_status(StatusCode::BAD_REQUEST)
+                    .respond_json(&json!({
+                        "errors": [{ "message": "PersistedQueryNotFound", "extensions": { "code": "PERSISTED_QUERY_NOT_FOUND" } }]
+                    })),
+            )
+            .expect(
+                Expectation::new(Method::POST, "/graphql")
+                    .json_body(&json!({ "query": "{ a }", "extensions": extensions }))
+                    .times(1)
+                    .respond_json(&json!({ "data": { "a": 1 } })),
+            );
+
+        let data: Data = GraphQLQuery::new("{ a }")
+            .persist()
+            .path("http://localhost/graphql")
+            .query(&client)
+            .await
+            .unwrap();
+        assert_eq!(data, Data { a: 1 });
+    }
+
+    #[tokio::test]
+    async fn test_errors_of_error_responses_are_graphql_errors() {
+        let client = MockClient::new();
+        client
+            .expect(
+                Expectation::new(Method::POST, "/graphql")
+                    .times(1)
+                    .respond_status(StatusCode::FORBIDDEN)
+                    .respond_json(&json!({ "errors": [{ "message": "forbidden" }, { "message": "gone", "extensions": { "status": 410 } }] })),
+            )
+            .expect(
+                Expectation::new(Method::POST, "/graphql")
+                    .times(1)
+                    .respond_status(StatusCode::BAD_GATEWAY)
+                    .respond_body("upstream unavailable"),
+            );
+
+        let errors = match GraphQLQuery::new("{ a }")
+            .path("http://localhost/graphql")
+            .query(&client)
+            .await
+        {
+            Err::<Data, _>(BaseClientError::GraphQL(errors)) => errors,
+            result => panic!("expected graphql errors, got {result:?}"),
+        };
+        let statuses: Vec<_> = errors.iter().map(GraphQLError::status).collect();
+        assert_eq!(statuses, [Some(StatusCode::FORBIDDEN), Some(StatusCode::GONE)]);
+
+        assert!(matches!(
+            GraphQLQuery::new("{ a }")
+                .path("http://localhost/graphql")
+                .query(&client)
+                .await,
+            Err::<Data, _>(BaseClientError::Response {
+                status: StatusCode::BAD_GATEWAY,
+                ..
+            })
+        ));
+    }
+}
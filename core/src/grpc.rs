@@ -0,0 +1,145 @@
+use crate::{inject_context, set_trace_parent};
+use hyper::Request;
+use std::task::{Context, Poll};
+use tonic::metadata::MetadataMap;
+use tonic::service::Interceptor;
+use tower_layer::Layer;
+use tower_service::Service;
+use tracing::instrument::{Instrument, Instrumented};
+
+/// TraceContextInterceptor injects the current span's context (traceparent, tracestate and baggage)
+/// and request id into the metadata of outgoing gRPC requests, the same way a Client does for its
+/// requests' headers; e.g. `GreeterClient::with_interceptor(channel, TraceContextInterceptor)`
+#[derive(Clone, Copy, Debug, Default)]
+pub struct TraceContextInterceptor;
+
+impl Interceptor for TraceContextInterceptor {
+    fn call(&mut self, mut request: tonic::Request<()>) -> Result<tonic::Request<()>, tonic::Status> {
+        let mut headers = std::mem::take(request.metadata_mut()).into_headers();
+        inject_context(&mut headers);
+        *request.metadata_mut() = MetadataMap::from_headers(headers);
+        Ok(request)
+    }
+}
+
+/// GrpcTraceLayer handles each request within a span continuing the trace propagated in the
+/// request's metadata, see set_trace_parent; e.g. `Server::builder().layer(GrpcTraceLayer)`
+#[derive(Clone, Copy, Debug, Default)]
+pub struct GrpcTraceLayer;
+
+impl<S> Layer<S> for GrpcTraceLayer {
+    type Service = GrpcTraceService<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        GrpcTraceService { inner }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct GrpcTraceService<S> {
+    inner: S,
+}
+
+impl<S, B> Service<Request<B>> for GrpcTraceService<S>
+where
+    S: Service<Request<B>>,
+{
+    type Response = S::Response;
+    type Error = S::Error;
+    type Future = Instrumented<S::Future>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, request: Request<B>) -> Self::Future {
+        let span = make_grpc_span(&request);
+        span.in_scope(|| self.inner.call(request)).instrument(span)
+    }
+}
+
+/// gRPC requests are made to `/{package.Service}/{Method}`
+fn make_grpc_span<B>(request: &Request<B>) -> tracing::Span {
+    let path = request.uri().path();
+    let (service, method) = path.trim_start_matches('/').split_once('/').unwrap_or((path, ""));
+    set_trace_parent(
+        request.headers(),
+        tracing::info_span!(
+            target: "",
+            "request",
+            "otel.name" = path.trim_start_matches('/'),
+            "otel.kind" = "server",
+            "rpc.system" = "grpc",
+            "rpc.service" = service,
+            "rpc.method" = method,
+        ),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{set_request_id, traceparent, X_REQUEST_ID};
+    use hyper::header::HeaderValue;
+    use opentelemetry::trace::TracerProvider as _;
+    use opentelemetry_sdk::trace::TracerProvider;
+    use std::convert::Infallible;
+    use std::future::{ready, Ready};
+    use tracing_subscriber::{layer::SubscriberExt, Registry};
+
+    const TRACE_ID: &str = "0af7651916cd43dd8448eb211c80319c";
+
+    /// the tracer provider must outlive the subscriber, its tracer only holds a weak reference to it
+    fn subscriber(provider: &TracerProvider) -> impl tracing::Subscriber + Send + Sync {
+        Registry::default().with(tracing_opentelemetry::layer().with_tracer(provider.tracer("test")))
+    }
+
+    /// responds with the traceparent of the span the request is handled in
+    struct Traceparent;
+
+    impl<B> Service<Request<B>> for Traceparent {
+        type Response = Option<String>;
+        type Error = Infallible;
+        type Future = Ready<Result<Self::Response, Self::Error>>;
+
+        fn poll_ready(&mut self, _: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+            Poll::Ready(Ok(()))
+        }
+
+        fn call(&mut self, _: Request<B>) -> Self::Future {
+            ready(Ok(traceparent()))
+        }
+    }
+
+    #[test]
+    fn test_interceptor_injects_trace_context_and_request_id() {
+        let provider = TracerProvider::builder().build();
+        let _guard = tracing::subscriber::set_default(subscriber(&provider));
+        let span = tracing::info_span!("request");
+        set_request_id(&span, HeaderValue::from_static("request-1"));
+        let _entered = span.enter();
+
+        let request = TraceContextInterceptor.call(tonic::Request::new(())).unwrap();
+        let metadata = request.metadata();
+        let expected = traceparent().unwrap();
+        assert_eq!(metadata.get("traceparent").unwrap().to_str().unwrap(), expected);
+        assert_eq!(
+            metadata.get(X_REQUEST_ID.as_str()).unwrap().to_str().unwrap(),
+            "request-1"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_service_continues_propagated_trace() {
+        let provider = TracerProvider::builder().build();
+        let _guard = tracing::subscriber::set_default(subscriber(&provider));
+        let request = Request::post("/helloworld.Greeter/SayHello")
+            .header("traceparent", format!("00-{TRACE_ID}-b7ad6b7169203331-01"))
+            .body(())
+            .unwrap();
+
+        let traceparent = GrpcTraceLayer.layer(Traceparent).call(request).await.unwrap().unwrap();
+        assert!(traceparent.starts_with(&format!("00-{TRACE_ID}-")), "{traceparent}");
+        assert!(!traceparent.contains("b7ad6b7169203331"), "{traceparent}");
+    }
+}
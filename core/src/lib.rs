@@ -1,9 +1,3 @@
-#![cfg_attr(feature = "client", allow(incomplete_features))]
-#![cfg_attr(
-    feature = "client",
-    feature(associated_type_defaults, const_caller_location, specialization,)
-)]
-
 #[cfg(not(any(feature = "anyhow", feature = "color-eyre")))]
 compile_error!("One of `anyhow` or `color-eyre` features must be enabled.");
 #[cfg(all(feature = "anyhow", feature = "color-eyre"))]
@@ -44,8 +38,45 @@ pub use paste::paste as service_util_paste;
 
 cfg_if! {
     if #[cfg(feature = "client")] {
+        mod auth;
+        mod cache;
+        mod circuit_breaker;
         mod client;
+        mod graphql;
+        mod rate_limit;
+        pub use auth::*;
+        pub use cache::*;
+        pub use circuit_breaker::*;
         pub use client::*;
+        pub use graphql::*;
+        pub use rate_limit::*;
+
+        pub use hyper as service_util_hyper;
+        pub use serde as service_util_serde;
+    }
+}
+cfg_if! {
+    if #[cfg(feature = "grpc")] {
+        mod grpc;
+        pub use grpc::*;
+    }
+}
+cfg_if! {
+    if #[cfg(feature = "metrics")] {
+        mod metrics;
+        pub use metrics::*;
+    }
+}
+cfg_if! {
+    if #[cfg(feature = "mock")] {
+        mod mock;
+        pub use mock::*;
+    }
+}
+cfg_if! {
+    if #[cfg(feature = "signing")] {
+        mod signing;
+        pub use signing::*;
     }
 }
 cfg_if! {
@@ -0,0 +1,173 @@
+use hyper::service::{make_service_fn, service_fn};
+use hyper::{header::CONTENT_TYPE, Body, Method, Response, Server, StatusCode};
+use opentelemetry::metrics::{Counter, Histogram, MetricsError, Unit, UpDownCounter};
+use opentelemetry::KeyValue;
+use opentelemetry_sdk::metrics::MeterProvider;
+use prometheus::{Encoder, Registry, TextEncoder};
+use std::convert::Infallible;
+use std::net::SocketAddr;
+use std::time::Instant;
+
+/// instruments are created from the global meter provider for every request rather than once,
+/// so that requests are recorded with whichever meter provider is installed at the time
+struct ClientMetrics {
+    duration: Histogram<f64>,
+    active_requests: UpDownCounter<i64>,
+    responses: Counter<u64>,
+}
+
+impl ClientMetrics {
+    fn new() -> Self {
+        let meter = opentelemetry::global::meter("service-util");
+        Self {
+            duration: meter
+                .f64_histogram("http.client.request.duration")
+                .with_unit(Unit::new("s"))
+                .with_description("Duration of outbound requests until the response headers were received")
+                .init(),
+            active_requests: meter
+                .i64_up_down_counter("http.client.active_requests")
+                .with_description("Number of outbound requests in flight")
+                .init(),
+            responses: meter
+                .u64_counter("http.client.responses")
+                .with_description("Number of outbound requests by response status class")
+                .init(),
+        }
+    }
+}
+
+/// RequestMetrics records a single outbound request, a request dropped before it completed is
+/// only removed from the in-flight requests
+pub(crate) struct RequestMetrics {
+    metrics: ClientMetrics,
+    attributes: Vec<KeyValue>,
+    start: Instant,
+    completed: bool,
+}
+
+impl RequestMetrics {
+    pub(crate) fn start(method: &Method, route: &'static str, base_uri: Option<&str>) -> Self {
+        let mut attributes = vec![
+            KeyValue::new("http.request.method", method.to_string()),
+            KeyValue::new("http.route", route),
+        ];
+        if let Some(base_uri) = base_uri {
+            attributes.push(KeyValue::new("client.base_uri", base_uri.to_string()));
+        }
+        let metrics = ClientMetrics::new();
+        metrics.active_requests.add(1, &attributes);
+        Self {
+            metrics,
+            attributes,
+            start: Instant::now(),
+            completed: false,
+        }
+    }
+
+    /// `status` is None if no response was received, e.g. on network errors or timeouts
+    pub(crate) fn complete(mut self, status: Option<StatusCode>) {
+        self.completed = true;
+        self.metrics.active_requests.add(-1, &self.attributes);
+
+        let status_class = match status.map(|status| status.as_u16() / 100) {
+            Some(1) => "1xx",
+            Some(2) => "2xx",
+            Some(3) => "3xx",
+            Some(4) => "4xx",
+            Some(5) => "5xx",
+            _ => "error",
+        };
+        let mut attributes = self.attributes.clone();
+        attributes.push(KeyValue::new("http.response.status_class", status_class));
+        self.metrics
+            .duration
+            .record(self.start.elapsed().as_secs_f64(), &attributes);
+        self.metrics.responses.add(1, &attributes);
+    }
+}
+
+impl Drop for RequestMetrics {
+    fn drop(&mut self) {
+        if !self.completed {
+            self.metrics.active_requests.add(-1, &self.attributes);
+        }
+    }
+}
+
+/// PrometheusMetrics holds the registry which metrics are exported to once installed with
+/// install_prometheus_metrics, the registry can be scraped either by mounting PrometheusMetrics::encode
+/// on an existing server's route or by running PrometheusMetrics::serve
+#[derive(Clone, Debug)]
+pub struct PrometheusMetrics {
+    registry: Registry,
+    meter_provider: MeterProvider,
+}
+
+/// sets up a meter provider exporting to a new Prometheus registry as the global meter provider,
+/// client metrics of requests started from then on are exported to the registry
+pub fn install_prometheus_metrics() -> Result<PrometheusMetrics, MetricsError> {
+    let registry = Registry::new();
+    let exporter = opentelemetry_prometheus::exporter()
+        .with_registry(registry.clone())
+        .build()?;
+    let meter_provider = MeterProvider::builder().with_reader(exporter).build();
+    opentelemetry::global::set_meter_provider(meter_provider.clone());
+    Ok(PrometheusMetrics {
+        registry,
+        meter_provider,
+    })
+}
+
+impl PrometheusMetrics {
+    pub fn registry(&self) -> &Registry {
+        &self.registry
+    }
+
+    pub fn meter_provider(&self) -> &MeterProvider {
+        &self.meter_provider
+    }
+
+    /// the registry's metrics in the Prometheus text exposition format
+    pub fn encode(&self) -> String {
+        let mut buffer = vec![];
+        if let Err(err) = TextEncoder::new().encode(&self.registry.gather(), &mut buffer) {
+            tracing::error!("could not encode metrics: {err}");
+        }
+        String::from_utf8(buffer).unwrap_or_default()
+    }
+
+    /// serves the registry's metrics on every path of the given address, e.g. `127.0.0.1:9464`
+    pub async fn serve(self, addr: SocketAddr) -> Result<(), hyper::Error> {
+        let make_service = make_service_fn(move |_| {
+            let metrics = self.clone();
+            async move {
+                Ok::<_, Infallible>(service_fn(move |_| {
+                    let response = Response::builder()
+                        .header(CONTENT_TYPE, TextEncoder::new().format_type())
+                        .body(Body::from(metrics.encode()))
+                        .unwrap();
+                    async move { Ok::<_, Infallible>(response) }
+                }))
+            }
+        });
+        Server::bind(&addr).serve(make_service).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_requests_are_recorded_with_a_meter_provider_installed_later() {
+        RequestMetrics::start(&Method::GET, "/before", None).complete(Some(StatusCode::OK));
+
+        let metrics = install_prometheus_metrics().unwrap();
+        RequestMetrics::start(&Method::GET, "/after", None).complete(Some(StatusCode::OK));
+
+        let encoded = metrics.encode();
+        assert!(encoded.contains(r#"http_route="/after""#), "{encoded}");
+        assert!(!encoded.contains(r#"http_route="/before""#), "{encoded}");
+    }
+}
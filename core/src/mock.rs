@@ -0,0 +1,658 @@
+use crate::{serialize_params, AuthProvider, BaseClientError, Client};
+use async_trait::async_trait;
+use hyper::body::to_bytes;
+use hyper::http::header::{
+    HeaderMap, HeaderName, HeaderValue, CONTENT_LENGTH, CONTENT_TYPE, SET_COOKIE, TRANSFER_ENCODING,
+};
+use hyper::{Body, Method, Request, Response, StatusCode};
+use serde::{Deserialize, Serialize};
+use std::fmt::Debug;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use thiserror::Error;
+
+/// MockClient answers requests with the canned response of the first matching Expectation
+/// which has not yet been exhausted (or, if in order, with that of the first expectation not
+/// yet exhausted provided it matches), requests matching no expectation are answered with a
+/// 501 Not Implemented; when dropped, panics if any expectation was not hit (or was not hit
+/// the expected number of times) or if any request went unmatched
+#[derive(Debug, Default)]
+pub struct MockClient {
+    headers: HeaderMap,
+    expectations: Mutex<Vec<Expectation>>,
+    unmatched: Mutex<Vec<String>>,
+    in_order: bool,
+}
+
+/// replaces credentials in recorded fixtures, a redacted query param matches any value on replay
+const REDACTED: &str = "REDACTED";
+
+#[derive(Clone, Debug)]
+pub struct Expectation {
+    method: Method,
+    path: String,
+    query: Option<String>,
+    headers: HeaderMap,
+    body: Option<ExpectedBody>,
+    times: Option<usize>,
+    hits: usize,
+    response: MockResponse,
+}
+
+#[derive(Clone, Debug)]
+enum ExpectedBody {
+    Bytes(Vec<u8>),
+    Json(serde_json::Value),
+}
+
+#[derive(Clone, Debug)]
+struct MockResponse {
+    status: StatusCode,
+    headers: HeaderMap,
+    body: Vec<u8>,
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct Fixture {
+    pub request: FixtureRequest,
+    pub response: FixtureResponse,
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct FixtureRequest {
+    #[serde(with = "method")]
+    pub method: Method,
+    /// the request's path and query
+    pub uri: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub body: Option<FixtureBody>,
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct FixtureResponse {
+    pub status: u16,
+    #[serde(default)]
+    pub headers: Vec<(String, String)>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub body: Option<FixtureBody>,
+}
+
+/// json bodies are stored as json so that fixtures stay readable and editable,
+/// bodies which aren't valid utf-8 are stored base64 encoded
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum FixtureBody {
+    Json(serde_json::Value),
+    Text(String),
+    Base64(String),
+}
+
+/// RecordingClient sends requests with the wrapped client and records each exchange,
+/// the recorded exchanges are written as fixtures to `path` when the RecordingClient is dropped
+/// (or when calling RecordingClient::save) and can then be played back with MockClient::replay;
+/// request headers are not recorded and the values of Set-Cookie response headers and of the
+/// query params named by the wrapped client's AuthProvider::sensitive_params are redacted
+/// so that credentials don't end up in fixture files
+#[derive(Debug)]
+pub struct RecordingClient<C> {
+    client: C,
+    path: PathBuf,
+    fixtures: Mutex<Vec<Fixture>>,
+}
+
+#[derive(Debug, Error)]
+pub enum FixtureError {
+    #[error("could not read / write fixtures: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("could not deserialize / serialize fixtures: {0}")]
+    Serde(#[from] serde_json::Error),
+    #[error("invalid fixture: {0}")]
+    Invalid(String),
+}
+
+impl MockClient {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// headers returned from Client::headers, i.e. sent with every request
+    pub fn with_headers(mut self, headers: HeaderMap) -> Self {
+        self.headers = headers;
+        self
+    }
+
+    /// expects requests in the order the expectations were added: a request is only matched
+    /// against the first expectation which has not yet been exhausted
+    pub fn in_order(mut self) -> Self {
+        self.in_order = true;
+        self
+    }
+
+    pub fn expect(&self, expectation: Expectation) -> &Self {
+        self.expectations.lock().unwrap().push(expectation);
+        self
+    }
+
+    /// loads fixtures previously captured with a RecordingClient, each fixture is
+    /// expected to be requested exactly once and in the recorded order
+    pub fn replay(path: impl AsRef<Path>) -> Result<Self, FixtureError> {
+        let fixtures: Vec<Fixture> = serde_json::from_slice(&std::fs::read(path)?)?;
+        let client = Self::new().in_order();
+        for fixture in fixtures {
+            client.expect(Expectation::try_from(fixture)?);
+        }
+        Ok(client)
+    }
+
+    /// panics if any expectation has not been hit the expected number of times
+    /// or if any request did not match an expectation
+    pub fn verify(&self) {
+        let mut failures = vec![];
+        for expectation in self.expectations.lock().unwrap().iter() {
+            let satisfied = match expectation.times {
+                Some(times) => expectation.hits == times,
+                None => expectation.hits > 0,
+            };
+            if !satisfied {
+                failures.push(format!(
+                    "expected {} {}{} {} time(s), received {}",
+                    expectation.method,
+                    expectation.path,
+                    expectation
+                        .query
+                        .as_ref()
+                        .map(|query| format!("?{query}"))
+                        .unwrap_or_default(),
+                    expectation
+                        .times
+                        .map(|times| times.to_string())
+                        .unwrap_or_else(|| "1+".into()),
+                    expectation.hits,
+                ));
+            }
+        }
+        for unmatched in self.unmatched.lock().unwrap().iter() {
+            failures.push(format!("unexpected request {unmatched}"));
+        }
+        if !failures.is_empty() {
+            panic!("MockClient expectations not met:\n{}", failures.join("\n"));
+        }
+    }
+}
+
+impl Drop for MockClient {
+    fn drop(&mut self) {
+        if !std::thread::panicking() {
+            self.verify();
+        }
+    }
+}
+
+#[async_trait]
+impl Client for MockClient {
+    type Error = BaseClientError;
+
+    fn headers(&self) -> &HeaderMap {
+        &self.headers
+    }
+    fn is_transport_error(&self, error: &Self::Error) -> bool {
+        error.is_transport()
+    }
+    async fn rest(&self, request: Request<Body>) -> Result<Response<Body>, Self::Error> {
+        let (parts, body) = request.into_parts();
+        let body = to_bytes(body)
+            .await
+            .map_err(|err| BaseClientError::RequestBodyBuild(format!("{err}")))?;
+        let request = Request::from_parts(parts, body.to_vec());
+
+        let mut expectations = self.expectations.lock().unwrap();
+        let expectation = match self.in_order {
+            true => expectations
+                .iter_mut()
+                .find(|expectation| !expectation.is_exhausted())
+                .filter(|expectation| expectation.matches(&request)),
+            false => expectations
+                .iter_mut()
+                .find(|expectation| expectation.matches(&request)),
+        };
+        match expectation {
+            Some(expectation) => {
+                expectation.hits += 1;
+                Ok(expectation.response.to_response())
+            }
+            None => {
+                let description = format!(
+                    "{} {}",
+                    request.method(),
+                    request.uri().path_and_query().map(|x| x.as_str()).unwrap_or_default()
+                );
+                let message = format!("no expectation matched request {description}");
+                self.unmatched.lock().unwrap().push(description);
+                Ok(Response::builder()
+                    .status(StatusCode::NOT_IMPLEMENTED)
+                    .body(Body::from(message))
+                    .unwrap())
+            }
+        }
+    }
+}
+
+impl Expectation {
+    /// expects a request with the given method and path (excluding the query),
+    /// by default expects at least one matching request and responds with 200 OK and an empty body
+    pub fn new(method: Method, path: impl Into<String>) -> Self {
+        Self {
+            method,
+            path: path.into(),
+            query: None,
+            headers: HeaderMap::default(),
+            body: None,
+            times: None,
+            hits: 0,
+            response: MockResponse {
+                status: StatusCode::OK,
+                headers: HeaderMap::default(),
+                body: vec![],
+            },
+        }
+    }
+
+    /// expects the query to equal the params serialized the same way as Endpoint::params
+    pub fn params(mut self, params: &impl Serialize) -> Self {
+        self.query = Some(serialize_params(params).expect("could not serialize expected params"));
+        self
+    }
+
+    /// expects the request to include this header, other headers are ignored
+    pub fn header(mut self, name: HeaderName, value: HeaderValue) -> Self {
+        self.headers.append(name, value);
+        self
+    }
+
+    pub fn body(mut self, body: impl Into<Vec<u8>>) -> Self {
+        self.body = Some(ExpectedBody::Bytes(body.into()));
+        self
+    }
+
+    /// expects the request body to be json equal to `body`, regardless of formatting
+    pub fn json_body(mut self, body: &impl Serialize) -> Self {
+        self.body = Some(ExpectedBody::Json(
+            serde_json::to_value(body).expect("could not serialize expected body"),
+        ));
+        self
+    }
+
+    /// expects exactly `times` matching requests, further matching requests
+    /// fall through to later expectations
+    pub fn times(mut self, times: usize) -> Self {
+        self.times = Some(times);
+        self
+    }
+
+    pub fn respond_status(mut self, status: StatusCode) -> Self {
+        self.response.status = status;
+        self
+    }
+
+    pub fn respond_header(mut self, name: HeaderName, value: HeaderValue) -> Self {
+        self.response.headers.append(name, value);
+        self
+    }
+
+    pub fn respond_body(mut self, body: impl Into<Vec<u8>>) -> Self {
+        self.response.body = body.into();
+        self
+    }
+
+    pub fn respond_json(mut self, body: &impl Serialize) -> Self {
+        self.response.body = serde_json::to_vec(body).expect("could not serialize response body");
+        self.response
+            .headers
+            .insert(CONTENT_TYPE, HeaderValue::from_static("application/json"));
+        self
+    }
+
+    fn is_exhausted(&self) -> bool {
+        self.times.is_some_and(|times| self.hits >= times)
+    }
+
+    fn matches(&self, request: &Request<Vec<u8>>) -> bool {
+        if self.is_exhausted() {
+            return false;
+        }
+        if request.method() != self.method || request.uri().path() != self.path {
+            return false;
+        }
+        if let Some(query) = &self.query {
+            if !query_matches(query, request.uri().query().unwrap_or_default()) {
+                return false;
+            }
+        }
+        for (name, value) in self.headers.iter() {
+            if !request.headers().get_all(name).iter().any(|x| x == value) {
+                return false;
+            }
+        }
+        match &self.body {
+            None => true,
+            Some(ExpectedBody::Bytes(body)) => request.body() == body,
+            Some(ExpectedBody::Json(body)) => {
+                serde_json::from_slice::<serde_json::Value>(request.body()).is_ok_and(|x| &x == body)
+            }
+        }
+    }
+}
+
+/// compares queries param by param, an expected param with a redacted value matches any value
+fn query_matches(expected: &str, query: &str) -> bool {
+    if expected == query {
+        return true;
+    }
+    let expected = expected.split('&');
+    let mut query = query.split('&');
+    expected
+        .zip(query.by_ref())
+        .all(|(expected, param)| match expected.split_once('=') {
+            Some((name, REDACTED)) => param.split_once('=').is_some_and(|(x, _)| x == name),
+            _ => expected == param,
+        })
+        && query.next().is_none()
+}
+
+impl MockResponse {
+    fn to_response(&self) -> Response<Body> {
+        let mut response = Response::new(Body::from(self.body.clone()));
+        *response.status_mut() = self.status;
+        *response.headers_mut() = self.headers.clone();
+        response
+    }
+}
+
+impl TryFrom<Fixture> for Expectation {
+    type Error = FixtureError;
+
+    fn try_from(fixture: Fixture) -> Result<Self, Self::Error> {
+        let uri = fixture
+            .request
+            .uri
+            .parse::<hyper::Uri>()
+            .map_err(|err| FixtureError::Invalid(format!("{err}")))?;
+
+        let mut expectation = Self::new(fixture.request.method, uri.path()).times(1);
+        expectation.query = Some(uri.query().unwrap_or_default().into());
+        expectation.body = match fixture.request.body {
+            None => Some(ExpectedBody::Bytes(vec![])),
+            Some(FixtureBody::Json(body)) => Some(ExpectedBody::Json(body)),
+            Some(body) => Some(ExpectedBody::Bytes(body.into_bytes()?)),
+        };
+
+        expectation.response.status =
+            StatusCode::from_u16(fixture.response.status).map_err(|err| FixtureError::Invalid(format!("{err}")))?;
+        for (name, value) in fixture.response.headers {
+            expectation.response.headers.append(
+                HeaderName::try_from(name).map_err(|err| FixtureError::Invalid(format!("{err}")))?,
+                HeaderValue::try_from(value).map_err(|err| FixtureError::Invalid(format!("{err}")))?,
+            );
+        }
+        expectation.response.body = match fixture.response.body {
+            Some(body) => body.into_bytes()?,
+            None => vec![],
+        };
+
+        Ok(expectation)
+    }
+}
+
+impl FixtureBody {
+    fn from_bytes(bytes: &[u8]) -> Option<Self> {
+        if bytes.is_empty() {
+            return None;
+        }
+        if let Ok(json) = serde_json::from_slice(bytes) {
+            return Some(Self::Json(json));
+        }
+        Some(match std::str::from_utf8(bytes) {
+            Ok(text) => Self::Text(text.into()),
+            Err(_) => Self::Base64(data_encoding::BASE64.encode(bytes)),
+        })
+    }
+
+    fn into_bytes(self) -> Result<Vec<u8>, FixtureError> {
+        match self {
+            Self::Json(json) => Ok(serde_json::to_vec(&json)?),
+            Self::Text(text) => Ok(text.into_bytes()),
+            Self::Base64(base64) => data_encoding::BASE64
+                .decode(base64.as_bytes())
+                .map_err(|err| FixtureError::Invalid(format!("{err}"))),
+        }
+    }
+}
+
+impl<C> RecordingClient<C> {
+    pub fn new(client: C, path: impl Into<PathBuf>) -> Self {
+        Self {
+            client,
+            path: path.into(),
+            fixtures: Mutex::default(),
+        }
+    }
+
+    pub fn save(&self) -> Result<(), FixtureError> {
+        let fixtures = self.fixtures.lock().unwrap();
+        if let Some(parent) = self.path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        std::fs::write(&self.path, serde_json::to_vec_pretty(&*fixtures)?)?;
+        Ok(())
+    }
+}
+
+impl<C> Drop for RecordingClient<C> {
+    fn drop(&mut self) {
+        if let Err(err) = self.save() {
+            log::error!("could not save recorded fixtures to {}: {err}", self.path.display());
+        }
+    }
+}
+
+#[async_trait]
+impl<C: Client + Send + Sync> Client for RecordingClient<C> {
+    type Error = C::Error;
+
+    fn headers(&self) -> &HeaderMap {
+        self.client.headers()
+    }
+    fn timeout(&self) -> Option<std::time::Duration> {
+        self.client.timeout()
+    }
+    fn max_response_size(&self) -> Option<usize> {
+        self.client.max_response_size()
+    }
+    fn base_uri(&self) -> Option<&str> {
+        self.client.base_uri()
+    }
+    fn auth(&self) -> Option<&dyn AuthProvider> {
+        self.client.auth()
+    }
+    fn is_transport_error(&self, error: &Self::Error) -> bool {
+        self.client.is_transport_error(error)
+    }
+    fn applies_timeout(&self) -> bool {
+        self.client.applies_timeout()
+    }
+    async fn rest(&self, request: Request<Body>) -> Result<Response<Body>, Self::Error> {
+        let (parts, body) = request.into_parts();
+        let body = to_bytes(body)
+            .await
+            .map_err(|err| BaseClientError::RequestBodyBuild(format!("{err}")))?;
+        let sensitive_params = self
+            .client
+            .auth()
+            .map(|auth| auth.sensitive_params())
+            .unwrap_or_default();
+        let fixture_request = FixtureRequest {
+            method: parts.method.clone(),
+            uri: redact_uri(&parts.uri, &sensitive_params),
+            body: FixtureBody::from_bytes(&body),
+        };
+
+        let response = self.client.rest(Request::from_parts(parts, Body::from(body))).await?;
+
+        let (parts, body) = response.into_parts();
+        let body = to_bytes(body)
+            .await
+            .map_err(BaseClientError::ResponseBodyInvalidCharacter)?;
+        self.fixtures.lock().unwrap().push(Fixture {
+            request: fixture_request,
+            response: FixtureResponse {
+                status: parts.status.as_u16(),
+                headers: parts
+                    .headers
+                    .iter()
+                    .filter(|(name, _)| *name != CONTENT_LENGTH && *name != TRANSFER_ENCODING)
+                    .filter_map(|(name, value)| {
+                        let value = value.to_str().ok()?;
+                        let value = match *name == SET_COOKIE {
+                            true => redact_cookie(value),
+                            false => value.to_string(),
+                        };
+                        Some((name.to_string(), value))
+                    })
+                    .collect(),
+                body: FixtureBody::from_bytes(&body),
+            },
+        });
+
+        Ok(Response::from_parts(parts, Body::from(body)))
+    }
+}
+
+/// the uri's path and query with the values of the sensitive query params redacted
+fn redact_uri(uri: &hyper::Uri, sensitive_params: &[&str]) -> String {
+    let path = uri.path();
+    let Some(query) = uri.query() else {
+        return path.into();
+    };
+    let query = query
+        .split('&')
+        .map(|param| match param.split_once('=') {
+            Some((name, _)) if sensitive_params.contains(&name) => format!("{name}={REDACTED}"),
+            _ => param.into(),
+        })
+        .collect::<Vec<_>>()
+        .join("&");
+    format!("{path}?{query}")
+}
+
+/// the Set-Cookie header value with the cookie's value redacted, its name and attributes are kept
+fn redact_cookie(set_cookie: &str) -> String {
+    let (cookie, attributes) = set_cookie
+        .split_once(';')
+        .map_or((set_cookie, None), |(x, y)| (x, Some(y)));
+    let name = cookie.split_once('=').map_or(cookie, |(name, _)| name);
+    match attributes {
+        Some(attributes) => format!("{name}={REDACTED};{attributes}"),
+        None => format!("{name}={REDACTED}"),
+    }
+}
+
+mod method {
+    use hyper::Method;
+    use serde::{de::Error, Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S: Serializer>(method: &Method, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(method.as_str())
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Method, D::Error> {
+        let method = String::deserialize(deserializer)?;
+        Method::from_bytes(method.as_bytes()).map_err(D::Error::custom)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ApiKey;
+
+    async fn get<C: Client>(client: &C, uri: &str) -> StatusCode {
+        let request = Request::get(uri).body(Body::empty()).unwrap();
+        client
+            .rest(request)
+            .await
+            .map_err(|err| format!("{err}"))
+            .unwrap()
+            .status()
+    }
+
+    fn fixtures_path(name: &str) -> PathBuf {
+        std::env::temp_dir().join(format!("service-util-{}-{name}.json", std::process::id()))
+    }
+
+    async fn record(path: &Path) {
+        let client = MockClient::new();
+        client.expect(Expectation::new(Method::GET, "/items").times(2).respond_header(
+            SET_COOKIE,
+            HeaderValue::from_static("session=secret-session; Path=/; HttpOnly"),
+        ));
+        let client = RecordingClient::new(client.with_auth(ApiKey::query("api_key", "secret-key")), path);
+        get(&client, "http://localhost/items?page=1&api_key=secret-key").await;
+        get(&client, "http://localhost/items?page=2&api_key=secret-key").await;
+    }
+
+    #[tokio::test]
+    async fn test_recorded_fixtures_are_redacted_and_replayed_in_order() {
+        let path = fixtures_path("replay-in-order");
+        record(&path).await;
+
+        let fixtures = std::fs::read_to_string(&path).unwrap();
+        assert!(!fixtures.contains("secret"), "{fixtures}");
+        assert!(fixtures.contains("/items?page=1&api_key=REDACTED"), "{fixtures}");
+        assert!(fixtures.contains("session=REDACTED; Path=/; HttpOnly"), "{fixtures}");
+
+        let client = MockClient::replay(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(
+            get(&client, "http://localhost/items?page=1&api_key=other-key").await,
+            StatusCode::OK
+        );
+        assert_eq!(
+            get(&client, "http://localhost/items?page=2&api_key=other-key").await,
+            StatusCode::OK
+        );
+    }
+
+    #[tokio::test]
+    #[should_panic(expected = "unexpected request GET /items?page=2")]
+    async fn test_replay_rejects_requests_out_of_order() {
+        let path = fixtures_path("replay-out-of-order");
+        record(&path).await;
+
+        let client = MockClient::replay(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(
+            get(&client, "http://localhost/items?page=2&api_key=secret-key").await,
+            StatusCode::NOT_IMPLEMENTED
+        );
+        get(&client, "http://localhost/items?page=1&api_key=secret-key").await;
+        get(&client, "http://localhost/items?page=2&api_key=secret-key").await;
+    }
+
+    #[tokio::test]
+    async fn test_params_are_serialized_like_endpoint_params() {
+        let client = MockClient::new();
+        client
+            .expect(
+                Expectation::new(Method::GET, "/items")
+                    .params(&serde_json::json!({ "page": 2 }))
+                    .times(1),
+            )
+            .expect(Expectation::new(Method::GET, "/items").params(&"filter=a%20b").times(1));
+
+        assert_eq!(get(&client, "http://localhost/items?page=2").await, StatusCode::OK);
+        assert_eq!(
+            get(&client, "http://localhost/items?filter=a%20b").await,
+            StatusCode::OK
+        );
+    }
+}
@@ -0,0 +1,399 @@
+use crate::{BaseClientError, Client, ClientMiddleware, Endpoint, EndpointName};
+use async_trait::async_trait;
+use hyper::http::header::{HeaderMap, HeaderName};
+use hyper::{Body, Request, Response};
+use std::collections::HashMap;
+use std::fmt::Debug;
+use std::sync::Mutex;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tokio::time::Instant;
+
+pub static X_RATELIMIT_REMAINING: HeaderName = HeaderName::from_static("x-ratelimit-remaining");
+pub static X_RATELIMIT_RESET: HeaderName = HeaderName::from_static("x-ratelimit-reset");
+
+/// a token bucket holding up to `burst` tokens (at least one), refilled at `requests` tokens every `per`
+#[derive(Clone, Copy, Debug)]
+pub struct RateLimit {
+    pub requests: u32,
+    pub per: Duration,
+    pub burst: u32,
+}
+
+impl RateLimit {
+    pub fn new(requests: u32, per: Duration) -> Self {
+        Self {
+            requests,
+            per,
+            burst: requests.max(1),
+        }
+    }
+
+    pub fn per_second(requests: u32) -> Self {
+        Self::new(requests, Duration::from_secs(1))
+    }
+
+    /// a bucket needs to hold at least one token for any request to pass, a burst of 0 is raised to 1
+    pub fn burst(self, burst: u32) -> Self {
+        Self {
+            burst: burst.max(1),
+            ..self
+        }
+    }
+
+    /// tokens refilled per second
+    fn rate(&self) -> f64 {
+        self.requests as f64 / self.per.as_secs_f64()
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+pub enum RateLimitMode {
+    /// requests wait for a token, requests which would have to wait longer than
+    /// `max_wait` are rejected right away
+    Queue { max_wait: Option<Duration> },
+    /// requests without an available token are rejected with BaseClientError::RateLimited
+    Reject,
+}
+
+impl Default for RateLimitMode {
+    fn default() -> Self {
+        Self::Queue { max_wait: None }
+    }
+}
+
+/// RateLimiter is a ClientMiddleware applying a token bucket limit to all requests made with
+/// the client and optionally further limits to the requests of individual endpoints;
+/// e.g. `client.with_middleware(RateLimiter::new(RateLimit::per_second(10)).endpoint::<GetUser>(RateLimit::per_second(2)))`
+///
+/// When adaptive, the client's bucket is drained according to the upstream's
+/// `X-RateLimit-Remaining` response header and, once no requests remain, holds requests back
+/// until the time given by the `X-RateLimit-Reset` response header
+#[derive(Debug)]
+pub struct RateLimiter {
+    mode: RateLimitMode,
+    adaptive: bool,
+    buckets: Mutex<Buckets>,
+}
+
+#[derive(Debug)]
+struct Buckets {
+    client: Bucket,
+    endpoints: HashMap<EndpointName, Bucket>,
+}
+
+#[derive(Debug)]
+struct Bucket {
+    limit: RateLimit,
+    tokens: f64,
+    /// tokens accrue from this instant on, may lie in the future when the upstream
+    /// asked to hold requests back
+    refilled_at: Instant,
+}
+
+impl RateLimiter {
+    pub fn new(limit: RateLimit) -> Self {
+        Self {
+            mode: RateLimitMode::default(),
+            adaptive: false,
+            buckets: Mutex::new(Buckets {
+                client: Bucket::new(limit),
+                endpoints: HashMap::default(),
+            }),
+        }
+    }
+
+    /// limits the requests made for endpoint E in addition to the client's limit
+    pub fn endpoint<E: Endpoint + ?Sized>(self, limit: RateLimit) -> Self {
+        self.buckets
+            .lock()
+            .unwrap()
+            .endpoints
+            .insert(EndpointName::of::<E>(), Bucket::new(limit));
+        self
+    }
+
+    pub fn mode(self, mode: RateLimitMode) -> Self {
+        Self { mode, ..self }
+    }
+
+    pub fn adaptive(self, adaptive: bool) -> Self {
+        Self { adaptive, ..self }
+    }
+
+    /// takes a token from the client's bucket and the endpoint's bucket (if it has one)
+    /// or returns how long to wait until both hold a token
+    fn try_acquire(&self, endpoint: Option<EndpointName>) -> Result<(), Duration> {
+        let now = Instant::now();
+        let mut buckets = self.buckets.lock().unwrap();
+        let Buckets { client, endpoints } = &mut *buckets;
+        let mut endpoint = endpoint.and_then(|endpoint| endpoints.get_mut(&endpoint));
+
+        let wait = endpoint
+            .as_mut()
+            .map(|endpoint| endpoint.wait(now))
+            .unwrap_or_default()
+            .max(client.wait(now));
+        if !wait.is_zero() {
+            return Err(wait);
+        }
+
+        client.take(now);
+        if let Some(endpoint) = endpoint {
+            endpoint.take(now);
+        }
+        Ok(())
+    }
+
+    async fn acquire(&self, endpoint: Option<EndpointName>) -> Result<(), BaseClientError> {
+        let deadline = match self.mode {
+            RateLimitMode::Queue { max_wait } => max_wait.map(|max_wait| Instant::now() + max_wait),
+            RateLimitMode::Reject => Some(Instant::now()),
+        };
+        loop {
+            let wait = match self.try_acquire(endpoint) {
+                Ok(()) => return Ok(()),
+                Err(wait) => wait,
+            };
+            let exceeds_deadline =
+                deadline.is_some_and(|deadline| Instant::now().checked_add(wait).is_none_or(|ready| ready > deadline));
+            if exceeds_deadline {
+                return Err(BaseClientError::RateLimited { retry_after: wait });
+            }
+            tracing::debug!(?wait, "rate limited, waiting for token");
+            tokio::time::sleep(wait).await;
+        }
+    }
+
+    fn adapt(&self, headers: &HeaderMap) {
+        let Some(remaining) = header_u64(headers, &X_RATELIMIT_REMAINING) else {
+            return;
+        };
+        let reset = header_u64(headers, &X_RATELIMIT_RESET).map(reset_in);
+
+        let now = Instant::now();
+        let mut buckets = self.buckets.lock().unwrap();
+        let client = &mut buckets.client;
+        client.refill(now);
+        client.tokens = client.tokens.min(remaining as f64);
+        if remaining == 0 {
+            if let Some(reset) = reset {
+                tracing::debug!(?reset, "upstream rate limit exhausted");
+                client.refilled_at = client.refilled_at.max(now + reset);
+            }
+        }
+    }
+}
+
+impl Bucket {
+    fn new(limit: RateLimit) -> Self {
+        let limit = limit.burst(limit.burst);
+        Self {
+            limit,
+            tokens: limit.burst as f64,
+            refilled_at: Instant::now(),
+        }
+    }
+
+    fn refill(&mut self, now: Instant) {
+        if now <= self.refilled_at {
+            return;
+        }
+        let elapsed = (now - self.refilled_at).as_secs_f64();
+        self.tokens = (self.tokens + elapsed * self.limit.rate()).min(self.limit.burst as f64);
+        self.refilled_at = now;
+    }
+
+    fn wait(&mut self, now: Instant) -> Duration {
+        self.refill(now);
+        let held_back = self.refilled_at.saturating_duration_since(now);
+        if held_back.is_zero() && self.tokens >= 1. {
+            return Duration::ZERO;
+        }
+        let rate = self.limit.rate();
+        if rate <= 0. {
+            return Duration::MAX;
+        }
+        let refill = Duration::try_from_secs_f64((1. - self.tokens).max(0.) / rate).unwrap_or(Duration::MAX);
+        held_back.saturating_add(refill)
+    }
+
+    fn take(&mut self, now: Instant) {
+        self.refill(now);
+        self.tokens -= 1.;
+    }
+}
+
+#[async_trait]
+impl<C: Client + Sync> ClientMiddleware<C> for RateLimiter {
+    async fn handle(&self, request: Request<Body>, client: &C) -> Result<Response<Body>, C::Error> {
+        let endpoint = request.extensions().get::<EndpointName>().copied();
+        self.acquire(endpoint).await?;
+
+        let response = client.rest(request).await?;
+        if self.adaptive {
+            self.adapt(response.headers());
+        }
+        Ok(response)
+    }
+}
+
+fn header_u64(headers: &HeaderMap, name: &HeaderName) -> Option<u64> {
+    headers.get(name)?.to_str().ok()?.trim().parse().ok()
+}
+
+/// X-RateLimit-Reset is given either as seconds until the reset or as a unix timestamp,
+/// values larger than a year in seconds are taken to be timestamps
+fn reset_in(reset: u64) -> Duration {
+    const YEAR_SECONDS: u64 = 365 * 24 * 60 * 60;
+    if reset <= YEAR_SECONDS {
+        return Duration::from_secs(reset);
+    }
+    let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default();
+    Duration::from_secs(reset).saturating_sub(now)
+}
+
+#[cfg(all(test, feature = "mock"))]
+mod tests {
+    use super::*;
+    use crate::{DefaultResponse, Expectation, MockClient, Path, ProblemDetails, Query};
+    use hyper::http::header::HeaderValue;
+    use hyper::Method;
+
+    #[derive(Debug)]
+    struct GetItems;
+
+    #[derive(Debug)]
+    struct GetUsers;
+
+    impl Endpoint for GetItems {
+        const METHOD: Method = Method::GET;
+        type Params<'a> = ();
+        type Body<'a> = ();
+        type Response<T> = DefaultResponse<T>;
+        type ErrorBody = ProblemDetails;
+
+        fn path(&self) -> Path {
+            "http://localhost/items".into()
+        }
+        fn params(&self) -> Self::Params<'_> {}
+    }
+
+    impl Endpoint for GetUsers {
+        const METHOD: Method = Method::GET;
+        type Params<'a> = ();
+        type Body<'a> = ();
+        type Response<T> = DefaultResponse<T>;
+        type ErrorBody = ProblemDetails;
+
+        fn path(&self) -> Path {
+            "http://localhost/users".into()
+        }
+        fn params(&self) -> Self::Params<'_> {}
+    }
+
+    fn mock_client(items: usize, users: usize) -> MockClient {
+        let client = MockClient::new();
+        if items > 0 {
+            client.expect(Expectation::new(Method::GET, "/items").times(items));
+        }
+        if users > 0 {
+            client.expect(Expectation::new(Method::GET, "/users").times(users));
+        }
+        client
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_queued_request_waits_for_a_token() {
+        let client = mock_client(3, 0).with_middleware(RateLimiter::new(RateLimit::per_second(2).burst(1)));
+
+        let start = Instant::now();
+        GetItems.ignore().query(&client).await.unwrap();
+        assert_eq!(start.elapsed(), Duration::ZERO);
+        GetItems.ignore().query(&client).await.unwrap();
+        assert_eq!(start.elapsed(), Duration::from_millis(500));
+        GetItems.ignore().query(&client).await.unwrap();
+        assert_eq!(start.elapsed(), Duration::from_secs(1));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_requests_without_a_token_are_rejected() {
+        let client =
+            mock_client(1, 0).with_middleware(RateLimiter::new(RateLimit::per_second(1)).mode(RateLimitMode::Reject));
+        GetItems.ignore().query(&client).await.unwrap();
+        assert!(matches!(
+            GetItems.ignore().query(&client).await,
+            Err(BaseClientError::RateLimited { retry_after }) if retry_after == Duration::from_secs(1),
+        ));
+
+        let limiter = RateLimiter::new(RateLimit::per_second(1)).mode(RateLimitMode::Queue {
+            max_wait: Some(Duration::from_millis(500)),
+        });
+        let client = mock_client(1, 0).with_middleware(limiter);
+        GetItems.ignore().query(&client).await.unwrap();
+        let start = Instant::now();
+        assert!(matches!(
+            GetItems.ignore().query(&client).await,
+            Err(BaseClientError::RateLimited { .. }),
+        ));
+        assert_eq!(start.elapsed(), Duration::ZERO);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_endpoint_limit_applies_only_to_its_endpoint() {
+        let limiter = RateLimiter::new(RateLimit::per_second(100)).endpoint::<GetItems>(RateLimit::per_second(1));
+        let client = mock_client(2, 3).with_middleware(limiter);
+
+        let start = Instant::now();
+        GetItems.ignore().query(&client).await.unwrap();
+        for _ in 0..3 {
+            GetUsers.ignore().query(&client).await.unwrap();
+        }
+        assert_eq!(start.elapsed(), Duration::ZERO);
+        GetItems.ignore().query(&client).await.unwrap();
+        assert_eq!(start.elapsed(), Duration::from_secs(1));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_exhausted_upstream_limit_holds_requests_back_until_reset() {
+        let client = MockClient::new();
+        client
+            .expect(
+                Expectation::new(Method::GET, "/items")
+                    .times(1)
+                    .respond_header(X_RATELIMIT_REMAINING.clone(), HeaderValue::from_static("0"))
+                    .respond_header(X_RATELIMIT_RESET.clone(), HeaderValue::from_static("5")),
+            )
+            .expect(Expectation::new(Method::GET, "/items"));
+        let client = client.with_middleware(RateLimiter::new(RateLimit::per_second(100)).adaptive(true));
+
+        let start = Instant::now();
+        GetItems.ignore().query(&client).await.unwrap();
+        GetItems.ignore().query(&client).await.unwrap();
+        assert!(start.elapsed() >= Duration::from_secs(5));
+        assert!(start.elapsed() < Duration::from_secs(6));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_zero_burst_lets_requests_through() {
+        let client = mock_client(2, 0).with_middleware(RateLimiter::new(RateLimit::per_second(1).burst(0)));
+        GetItems.ignore().query(&client).await.unwrap();
+        GetItems.ignore().query(&client).await.unwrap();
+
+        let mut bucket = Bucket::new(RateLimit::new(1, Duration::MAX));
+        bucket.take(Instant::now());
+        assert_eq!(bucket.wait(Instant::now()), Duration::MAX);
+    }
+
+    #[test]
+    fn test_reset_in_accepts_seconds_and_timestamps() {
+        assert_eq!(reset_in(30), Duration::from_secs(30));
+
+        let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_secs();
+        let reset = reset_in(now + 60);
+        assert!(
+            reset <= Duration::from_secs(60) && reset >= Duration::from_secs(58),
+            "{reset:?}"
+        );
+        assert_eq!(reset_in(now - 60), Duration::ZERO);
+    }
+}
@@ -46,7 +46,6 @@ const MAX_ALLOWED_REQUEST_BODY_SIZE: u64 = 1_073_741_824; // 1 GB
 
 pub static X_FORWARDED_FOR: HeaderName = HeaderName::from_static("x-forwarded-for");
 pub static X_REAL_IP: HeaderName = HeaderName::from_static("x-real-ip");
-pub static X_REQUEST_ID: HeaderName = HeaderName::from_static("x-request-id");
 
 static _X_REQUEST_ID: HeaderName = HeaderName::from_static("x-request-id");
 
@@ -0,0 +1,377 @@
+use hyper::http::header::{HeaderMap, HeaderName, CONTENT_TYPE};
+use hyper::{Method, StatusCode, Uri};
+use ring::{digest, hmac};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+#[cfg(feature = "client")]
+use crate::{BaseClientError, Client, ClientMiddleware};
+#[cfg(feature = "client")]
+use hyper::{http::header::HeaderValue, Response};
+#[cfg(any(
+    feature = "client",
+    all(feature = "server", any(feature = "axum-05", feature = "axum-06"))
+))]
+use hyper::{Body, Request};
+
+pub static X_SIGNATURE: HeaderName = HeaderName::from_static("x-signature");
+pub static X_SIGNATURE_TIMESTAMP: HeaderName = HeaderName::from_static("x-signature-timestamp");
+
+/// HmacSigner signs requests with an HMAC-SHA256 signature over the request's canonical form:
+/// ```text
+/// METHOD\n
+/// /path\n
+/// query params sorted by name (repeated params keep their order) and joined with &\n
+/// name:value\n for each signed header (in the configured order, value trimmed)
+/// timestamp\n
+/// hex encoded SHA-256 of the body
+/// ```
+/// the base64 encoded signature and the unix timestamp (in seconds) are sent in the signature and
+/// timestamp headers
+///
+/// As a ClientMiddleware it signs outgoing requests, add it before any other middleware modifying
+/// requests so that it signs the request as sent: `client.with_middleware(HmacSigner::new(secret))`;
+/// on the server, add it as an Extension and extract the request body with SignedBody
+#[derive(Clone, Debug)]
+pub struct HmacSigner {
+    key: hmac::Key,
+    signature_header: HeaderName,
+    timestamp_header: HeaderName,
+    signed_headers: Vec<HeaderName>,
+    max_age: Duration,
+}
+
+impl HmacSigner {
+    pub fn new(secret: impl AsRef<[u8]>) -> Self {
+        Self {
+            key: hmac::Key::new(hmac::HMAC_SHA256, secret.as_ref()),
+            signature_header: X_SIGNATURE.clone(),
+            timestamp_header: X_SIGNATURE_TIMESTAMP.clone(),
+            signed_headers: vec![CONTENT_TYPE],
+            max_age: Duration::from_secs(300),
+        }
+    }
+
+    pub fn signature_header(self, signature_header: HeaderName) -> Self {
+        Self {
+            signature_header,
+            ..self
+        }
+    }
+
+    pub fn timestamp_header(self, timestamp_header: HeaderName) -> Self {
+        Self {
+            timestamp_header,
+            ..self
+        }
+    }
+
+    /// headers included in the signature, defaults to Content-Type
+    pub fn signed_headers(self, signed_headers: impl IntoIterator<Item = HeaderName>) -> Self {
+        Self {
+            signed_headers: signed_headers.into_iter().collect(),
+            ..self
+        }
+    }
+
+    /// how far a request's timestamp may lie from the current time for it to be accepted
+    pub fn max_age(self, max_age: Duration) -> Self {
+        Self { max_age, ..self }
+    }
+
+    /// returns the base64 encoded signature of the request
+    pub fn sign(&self, method: &Method, uri: &Uri, headers: &HeaderMap, timestamp: u64, body: &[u8]) -> String {
+        let canonical = self.canonical_request(method, uri, headers, timestamp, body);
+        data_encoding::BASE64.encode(hmac::sign(&self.key, canonical.as_bytes()).as_ref())
+    }
+
+    /// checks the request's timestamp and signature headers, rejecting the request with
+    /// 401 Unauthorized if either is missing, the timestamp is stale or the signature doesn't match
+    pub fn verify(&self, method: &Method, uri: &Uri, headers: &HeaderMap, body: &[u8]) -> Result<(), crate::Error> {
+        let unauthorized = |msg: &str| crate::Error::msg(StatusCode::UNAUTHORIZED, msg);
+
+        let timestamp = headers
+            .get(&self.timestamp_header)
+            .and_then(|timestamp| timestamp.to_str().ok()?.parse::<u64>().ok())
+            .ok_or_else(|| unauthorized("missing or invalid signature timestamp"))?;
+        if unix_timestamp().abs_diff(timestamp) > self.max_age.as_secs() {
+            return Err(unauthorized("stale request signature"));
+        }
+
+        let signature = headers
+            .get(&self.signature_header)
+            .and_then(|signature| data_encoding::BASE64.decode(signature.as_bytes()).ok())
+            .ok_or_else(|| unauthorized("missing or invalid signature"))?;
+        let canonical = self.canonical_request(method, uri, headers, timestamp, body);
+        hmac::verify(&self.key, canonical.as_bytes(), &signature).map_err(|_| unauthorized("invalid signature"))
+    }
+
+    fn canonical_request(
+        &self,
+        method: &Method,
+        uri: &Uri,
+        headers: &HeaderMap,
+        timestamp: u64,
+        body: &[u8],
+    ) -> String {
+        let mut query = uri
+            .query()
+            .into_iter()
+            .flat_map(|query| query.split('&'))
+            .filter(|param| !param.is_empty())
+            .map(|param| param.split_once('=').unwrap_or((param, "")))
+            .collect::<Vec<_>>();
+        // a stable sort by name only, the order of a repeated param's values is significant
+        query.sort_by_key(|(name, _)| *name);
+        let query = query
+            .into_iter()
+            .map(|(name, value)| format!("{name}={value}"))
+            .collect::<Vec<_>>()
+            .join("&");
+
+        let mut canonical = format!("{method}\n{}\n{query}\n", uri.path());
+        for name in &self.signed_headers {
+            let value = headers
+                .get(name)
+                .map(|value| String::from_utf8_lossy(value.as_bytes()).trim().to_string())
+                .unwrap_or_default();
+            canonical.push_str(&format!("{name}:{value}\n"));
+        }
+        canonical.push_str(&format!(
+            "{timestamp}\n{}",
+            data_encoding::HEXLOWER.encode(digest::digest(&digest::SHA256, body).as_ref())
+        ));
+        canonical
+    }
+}
+
+#[cfg(feature = "client")]
+#[async_trait::async_trait]
+impl<C: Client + Sync> ClientMiddleware<C> for HmacSigner {
+    async fn handle(&self, request: Request<Body>, client: &C) -> Result<Response<Body>, C::Error> {
+        let (mut parts, body) = request.into_parts();
+        let body = hyper::body::to_bytes(body)
+            .await
+            .map_err(|err| BaseClientError::RequestBodyBuild(format!("{err}")))?;
+
+        let timestamp = unix_timestamp();
+        let signature = self.sign(&parts.method, &parts.uri, &parts.headers, timestamp, &body);
+        parts
+            .headers
+            .insert(self.timestamp_header.clone(), HeaderValue::from(timestamp));
+        parts.headers.insert(
+            self.signature_header.clone(),
+            HeaderValue::from_str(&signature).unwrap(),
+        );
+
+        client.rest(Request::from_parts(parts, Body::from(body))).await
+    }
+}
+
+/// SignedBody extracts the request body once the request's signature has been verified with the
+/// HmacSigner found in the request's extensions, must be the last extractor of a handler;
+/// the signature is verified against the request's OriginalUri if present, i.e. the uri as sent
+/// by the client rather than the one stripped of the prefix of a nested router
+#[cfg(all(feature = "server", any(feature = "axum-05", feature = "axum-06")))]
+#[derive(Clone, Debug)]
+pub struct SignedBody(pub Vec<u8>);
+
+#[cfg(all(feature = "server", feature = "axum-05"))]
+#[async_trait::async_trait]
+impl axum_05::extract::FromRequest<Body> for SignedBody {
+    type Rejection = crate::Error;
+
+    async fn from_request(request: &mut axum_05::extract::RequestParts<Body>) -> Result<Self, Self::Rejection> {
+        let signer = request
+            .extensions()
+            .get::<HmacSigner>()
+            .cloned()
+            .ok_or_else(|| crate::Error::default_details("HmacSigner extension is missing"))?;
+        let uri = request
+            .extensions()
+            .get::<axum_05::extract::OriginalUri>()
+            .map_or_else(|| request.uri().clone(), |uri| uri.0.clone());
+        let body = request
+            .take_body()
+            .ok_or_else(|| crate::Error::default_details("request body was already extracted"))?;
+        let body = crate::body_bytes(axum_05::extract::RawBody(body)).await?;
+        signer.verify(request.method(), &uri, request.headers(), &body)?;
+        Ok(Self(body))
+    }
+}
+
+#[cfg(all(feature = "server", feature = "axum-06"))]
+#[async_trait::async_trait]
+impl<S: Send + Sync> axum_06::extract::FromRequest<S, Body> for SignedBody {
+    type Rejection = crate::Error;
+
+    async fn from_request(request: Request<Body>, _: &S) -> Result<Self, Self::Rejection> {
+        let (parts, body) = request.into_parts();
+        let signer = parts
+            .extensions
+            .get::<HmacSigner>()
+            .ok_or_else(|| crate::Error::default_details("HmacSigner extension is missing"))?;
+        let uri = parts
+            .extensions
+            .get::<axum_06::extract::OriginalUri>()
+            .map_or(&parts.uri, |uri| &uri.0);
+        let body = crate::body_bytes(axum_06::extract::RawBody(body)).await?;
+        signer.verify(&parts.method, uri, &parts.headers, &body)?;
+        Ok(Self(body))
+    }
+}
+
+fn unix_timestamp() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SECRET: &str = "secret";
+
+    fn signed_headers(signer: &HmacSigner, uri: &str, timestamp: u64, body: &[u8]) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        headers.insert(CONTENT_TYPE, "application/json".parse().unwrap());
+        let signature = signer.sign(&Method::POST, &uri.parse().unwrap(), &headers, timestamp, body);
+        headers.insert(X_SIGNATURE_TIMESTAMP.clone(), timestamp.into());
+        headers.insert(X_SIGNATURE.clone(), signature.parse().unwrap());
+        headers
+    }
+
+    fn verify(signer: &HmacSigner, uri: &str, headers: &HeaderMap, body: &[u8]) -> Result<(), crate::Error> {
+        signer.verify(&Method::POST, &uri.parse().unwrap(), headers, body)
+    }
+
+    #[test]
+    fn test_signature_round_trip() {
+        let signer = HmacSigner::new(SECRET);
+        let headers = signed_headers(&signer, "/items?b=2&a=1", unix_timestamp(), b"{}");
+        assert!(verify(&signer, "/items?b=2&a=1", &headers, b"{}").is_ok());
+        // params are signed independently of their order
+        assert!(verify(&signer, "/items?a=1&b=2", &headers, b"{}").is_ok());
+        assert!(verify(&HmacSigner::new("other"), "/items?b=2&a=1", &headers, b"{}").is_err());
+    }
+
+    #[test]
+    fn test_stale_timestamp_is_rejected() {
+        let signer = HmacSigner::new(SECRET).max_age(Duration::from_secs(60));
+        let headers = signed_headers(&signer, "/items", unix_timestamp() - 61, b"{}");
+        assert!(verify(&signer, "/items", &headers, b"{}").is_err());
+    }
+
+    #[test]
+    fn test_tampered_request_is_rejected() {
+        let signer = HmacSigner::new(SECRET);
+        let headers = signed_headers(&signer, "/items?id=1&id=2", unix_timestamp(), b"{}");
+        assert!(verify(&signer, "/items?id=1&id=2", &headers, b"{}").is_ok());
+        assert!(verify(&signer, "/items?id=1&id=2", &headers, b"{\"admin\":true}").is_err());
+        assert!(verify(&signer, "/items?id=1&id=3", &headers, b"{}").is_err());
+        assert!(verify(&signer, "/items?id=2&id=1", &headers, b"{}").is_err());
+        assert!(verify(&signer, "/other?id=1&id=2", &headers, b"{}").is_err());
+
+        let mut tampered = headers.clone();
+        tampered.insert(CONTENT_TYPE, "text/plain".parse().unwrap());
+        assert!(verify(&signer, "/items?id=1&id=2", &tampered, b"{}").is_err());
+    }
+
+    #[test]
+    fn test_missing_headers_are_rejected() {
+        let signer = HmacSigner::new(SECRET);
+        let headers = signed_headers(&signer, "/items", unix_timestamp(), b"{}");
+        for name in [&X_SIGNATURE, &X_SIGNATURE_TIMESTAMP] {
+            let mut headers = headers.clone();
+            headers.remove(name);
+            let err = verify(&signer, "/items", &headers, b"{}").unwrap_err();
+            assert_eq!(err.status_code, StatusCode::UNAUTHORIZED);
+        }
+    }
+
+    #[cfg(all(feature = "client", feature = "mock"))]
+    mod client {
+        use super::*;
+        use crate::{Expectation, MockClient};
+
+        /// verifies the signature of requests before passing them on
+        #[derive(Debug)]
+        struct Verify(HmacSigner);
+
+        #[async_trait::async_trait]
+        impl<C: Client + Sync> ClientMiddleware<C> for Verify {
+            async fn handle(&self, request: Request<Body>, client: &C) -> Result<Response<Body>, C::Error> {
+                let (parts, body) = request.into_parts();
+                let body = hyper::body::to_bytes(body).await.unwrap();
+                assert!(parts.headers.contains_key(&X_SIGNATURE));
+                assert!(parts.headers.contains_key(&X_SIGNATURE_TIMESTAMP));
+                self.0.verify(&parts.method, &parts.uri, &parts.headers, &body).unwrap();
+                client.rest(Request::from_parts(parts, Body::from(body))).await
+            }
+        }
+
+        #[tokio::test]
+        async fn test_middleware_signs_requests() {
+            let client = MockClient::new();
+            client.expect(Expectation::new(Method::POST, "/items").body("{}"));
+            let client = client
+                .with_middleware(Verify(HmacSigner::new(SECRET)))
+                .with_middleware(HmacSigner::new(SECRET));
+
+            let request = Request::post("http://localhost/items?a=1")
+                .header(CONTENT_TYPE, "application/json")
+                .body(Body::from("{}"))
+                .unwrap();
+            let response = client.rest(request).await.unwrap();
+            assert_eq!(response.status(), StatusCode::OK);
+        }
+    }
+
+    #[cfg(all(feature = "server", any(feature = "axum-05", feature = "axum-06")))]
+    mod server {
+        use super::*;
+        use tower::Service;
+
+        async fn echo(SignedBody(body): SignedBody) -> String {
+            String::from_utf8(body).unwrap()
+        }
+
+        fn request(signed_uri: &str) -> Request<Body> {
+            let signer = HmacSigner::new(SECRET);
+            let mut request = Request::post("/api/items?a=1").body(Body::from("{}")).unwrap();
+            *request.headers_mut() = signed_headers(&signer, signed_uri, unix_timestamp(), b"{}");
+            request
+        }
+
+        #[cfg(feature = "axum-05")]
+        #[tokio::test]
+        async fn test_signed_body_of_nested_router_is_verified_against_original_uri() {
+            use axum_05::{routing::post, Extension, Router};
+
+            let mut router = Router::new()
+                .nest("/api", Router::new().route("/items", post(echo)))
+                .layer(Extension(HmacSigner::new(SECRET)));
+
+            let response = router.call(request("/api/items?a=1")).await.unwrap();
+            assert_eq!(response.status(), StatusCode::OK);
+            let response = router.call(request("/items?a=1")).await.unwrap();
+            assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+        }
+
+        #[cfg(feature = "axum-06")]
+        #[tokio::test]
+        async fn test_signed_body_of_nested_router_is_verified_against_original_uri() {
+            use axum_06::{routing::post, Extension, Router};
+
+            let mut router = Router::new()
+                .nest("/api", Router::new().route("/items", post(echo)))
+                .layer(Extension(HmacSigner::new(SECRET)));
+
+            let response = router.call(request("/api/items?a=1")).await.unwrap();
+            assert_eq!(response.status(), StatusCode::OK);
+            let response = router.call(request("/items?a=1")).await.unwrap();
+            assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+        }
+    }
+}
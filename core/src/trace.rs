@@ -1,16 +1,16 @@
 use crate::env;
 use ::chrono::Utc;
-use ::hyper::header::{HeaderMap, HeaderName};
-use ::opentelemetry::propagation::TextMapPropagator;
+use ::hyper::header::{HeaderMap, HeaderName, HeaderValue};
+use ::opentelemetry::propagation::{Extractor, Injector, TextMapPropagator};
 use ::opentelemetry::trace::TraceContextExt;
-use ::opentelemetry_sdk::propagation::TraceContextPropagator;
+use ::opentelemetry_sdk::propagation::{BaggagePropagator, TextMapCompositePropagator, TraceContextPropagator};
 use ::serde::*;
-use ::std::collections::HashMap;
 use ::tracing::Span;
 use ::tracing_error::ErrorLayer;
 use ::tracing_log::LogTracer;
 use ::tracing_opentelemetry::{OpenTelemetryLayer, OpenTelemetrySpanExt};
 use ::tracing_subscriber::filter::{targets::Targets, LevelFilter};
+use ::tracing_subscriber::registry::LookupSpan;
 use ::tracing_subscriber::{layer::SubscriberExt, EnvFilter, Registry};
 use ::tracing_tree::{time::FormatTime, HierarchicalLayer};
 
@@ -45,6 +45,8 @@ macro_rules! set_global_default {
 
 pub static TRACEPARENT: HeaderName = HeaderName::from_static("traceparent");
 pub static TRACESTATE: HeaderName = HeaderName::from_static("tracestate");
+pub static BAGGAGE: HeaderName = HeaderName::from_static("baggage");
+pub static X_REQUEST_ID: HeaderName = HeaderName::from_static("x-request-id");
 
 env! {
     JAEGER_SINK_KIND: JaegerSinkKind = JaegerSinkKind::Collector,
@@ -108,7 +110,10 @@ pub fn install_tracing() {
         },
     };
 
-    opentelemetry::global::set_text_map_propagator(TraceContextPropagator::new());
+    opentelemetry::global::set_text_map_propagator(TextMapCompositePropagator::new(vec![
+        Box::new(TraceContextPropagator::new()) as Box<dyn TextMapPropagator + Send + Sync>,
+        Box::new(BaggagePropagator::new()),
+    ]));
 }
 
 fn hierarchical_layer() -> HierarchicalLayer<fn() -> std::io::Stderr, UTCTime> {
@@ -154,26 +159,95 @@ fn jaeger_tracer() -> opentelemetry_sdk::trace::Tracer {
     }
 }
 
-pub fn set_trace_parent(headers: &HeaderMap, span: Span) -> Span {
-    let propagator = TraceContextPropagator::new();
-    if let Some(traceparent) = headers.get(&TRACEPARENT).and_then(|x| x.to_str().ok()) {
-        // Propagator::extract only works with HashMap<String, String>
-        let mut propagated_headers = match headers.get(&TRACESTATE).and_then(|x| x.to_str().ok()) {
-            Some(tracestate) => {
-                let mut propagated_headers = HashMap::with_capacity(2);
-                propagated_headers.insert("tracestate".to_string(), tracestate.to_string());
-                propagated_headers
-            }
-            None => HashMap::with_capacity(1),
-        };
-        propagated_headers.insert("traceparent".to_string(), traceparent.to_string());
+/// calls f with the global text map propagator, or with a TraceContextPropagator if none
+/// was installed (the global default is a noop propagator, which propagates no fields)
+fn with_propagator<T>(mut f: impl FnMut(&dyn TextMapPropagator) -> T) -> T {
+    opentelemetry::global::get_text_map_propagator(|propagator| match propagator.fields().next() {
+        Some(_) => f(propagator),
+        None => f(&TraceContextPropagator::new()),
+    })
+}
 
-        let context = propagator.extract(&propagated_headers);
+/// extracts the propagated context (traceparent, tracestate and baggage with the propagator
+/// registered by install_tracing, traceparent and tracestate only if no propagator was registered)
+/// from the headers and sets it as the span's parent,
+/// a forwarded x-request-id is associated with the span as well, see set_request_id
+pub fn set_trace_parent(headers: &HeaderMap, span: Span) -> Span {
+    if headers.contains_key(&TRACEPARENT) {
+        let context = with_propagator(|propagator| propagator.extract(&HeaderExtractor(headers)));
         span.set_parent(context);
     }
+    if let Some(request_id) = headers.get(&X_REQUEST_ID) {
+        set_request_id(&span, request_id.clone());
+    }
     span
 }
 
+/// injects the current span's context into the headers with the propagator registered by
+/// install_tracing (or a TraceContextPropagator if none was registered), along with the
+/// request id associated with the current span (if any)
+pub fn inject_context(headers: &mut HeaderMap) {
+    let context = Span::current().context();
+    with_propagator(|propagator| propagator.inject_context(&context, &mut HeaderInjector(headers)));
+    if let Some(request_id) = request_id() {
+        if !headers.contains_key(&X_REQUEST_ID) {
+            headers.insert(&X_REQUEST_ID, request_id);
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+struct PropagatedRequestId(HeaderValue);
+
+/// associates the request id with the span, requests made by a Client within the span
+/// or any of its descendants forward it in the x-request-id header
+pub fn set_request_id(span: &Span, request_id: HeaderValue) {
+    span.with_subscriber(|(id, dispatch)| {
+        if let Some(span) = dispatch
+            .downcast_ref::<Registry>()
+            .and_then(|registry| registry.span(id))
+        {
+            span.extensions_mut().insert(PropagatedRequestId(request_id));
+        }
+    });
+}
+
+/// the request id associated with the current span or its closest ancestor
+pub fn request_id() -> Option<HeaderValue> {
+    Span::current()
+        .with_subscriber(|(id, dispatch)| {
+            let span = dispatch.downcast_ref::<Registry>()?.span(id)?;
+            span.scope()
+                .find_map(|span| span.extensions().get::<PropagatedRequestId>().map(|x| x.0.clone()))
+        })
+        .flatten()
+}
+
+struct HeaderExtractor<'a>(&'a HeaderMap);
+
+impl Extractor for HeaderExtractor<'_> {
+    fn get(&self, key: &str) -> Option<&str> {
+        self.0.get(key).and_then(|value| value.to_str().ok())
+    }
+    fn keys(&self) -> Vec<&str> {
+        self.0.keys().map(HeaderName::as_str).collect()
+    }
+}
+
+struct HeaderInjector<'a>(&'a mut HeaderMap);
+
+impl Injector for HeaderInjector<'_> {
+    fn set(&mut self, key: &str, value: String) {
+        // e.g. TraceContextPropagator always sets tracestate, even when it's empty
+        if value.is_empty() {
+            return;
+        }
+        if let (Ok(name), Ok(value)) = (HeaderName::try_from(key), HeaderValue::try_from(value)) {
+            self.0.insert(name, value);
+        }
+    }
+}
+
 pub fn traceparent() -> Option<String> {
     let context = Span::current().context();
     let span_ref = context.span();
@@ -207,3 +281,35 @@ impl std::str::FromStr for JaegerSinkKind {
         })
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ::opentelemetry::trace::{SpanContext, SpanId, TraceFlags, TraceId, TraceState};
+    use ::opentelemetry::Context;
+
+    const TRACE_PARENT: &str = "00-0af7651916cd43dd8448eb211c80319c-b7ad6b7169203331-01";
+
+    #[test]
+    fn test_trace_context_is_propagated_without_global_propagator() {
+        let mut headers = HeaderMap::new();
+        headers.insert(&TRACEPARENT, HeaderValue::from_static(TRACE_PARENT));
+        let context = with_propagator(|propagator| propagator.extract(&HeaderExtractor(&headers)));
+        let span_context = context.span().span_context().clone();
+        assert!(span_context.is_remote());
+        assert_eq!(span_context.trace_id().to_string(), "0af7651916cd43dd8448eb211c80319c");
+
+        let span_context = SpanContext::new(
+            TraceId::from_hex("0af7651916cd43dd8448eb211c80319c").unwrap(),
+            SpanId::from_hex("b7ad6b7169203331").unwrap(),
+            TraceFlags::SAMPLED,
+            false,
+            TraceState::default(),
+        );
+        let context = Context::new().with_remote_span_context(span_context);
+        let mut headers = HeaderMap::new();
+        with_propagator(|propagator| propagator.inject_context(&context, &mut HeaderInjector(&mut headers)));
+        assert_eq!(headers.get(&TRACEPARENT).unwrap(), TRACE_PARENT);
+        assert!(!headers.contains_key(&TRACESTATE));
+    }
+}
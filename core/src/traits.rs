@@ -1,19 +1,75 @@
+use std::collections::HashMap;
+use std::fmt::Debug;
+
 pub trait Id {
     type Id: Clone;
     fn id(&self) -> Self::Id;
 }
 
-pub trait WithVariantUpdate: Id + Sized
+/// records are compared against their existing counterpart with PartialEq to tell updated
+/// records from unchanged ones
+pub trait WithVariantUpdate: Id + PartialEq + Sized
 where
     Self::Id: std::hash::Hash,
 {
-    type Variant: Split;
+    type Variant: Split + VariantIndex;
     type Split;
 
-    fn split(self, existing: &std::collections::HashMap<Self::Id, Self>) -> Self::Split;
+    fn split(records: impl IntoIterator<Item = Self>, existing: &HashMap<Self::Id, Self>) -> Self::Split;
 }
 
 pub trait Split: Sized {
     type Components: From<Self>;
     fn split(self) -> Self::Components;
 }
+
+/// records partitioned by how they compare against the existing record sharing their id
+#[derive(Derivative)]
+#[derivative(Clone(bound = "T: Clone"), Debug(bound = "T: Debug"), Default(bound = ""))]
+pub struct VariantUpdate<T> {
+    /// records with no existing counterpart
+    pub insert: Vec<T>,
+    /// records with the same variant as their existing counterpart but which are otherwise different
+    pub update: Vec<T>,
+    /// records equal to their existing counterpart
+    pub unchanged: Vec<T>,
+    /// records whose variant differs from that of their existing counterpart
+    pub variant_changed: Vec<T>,
+}
+
+/// identifies an enum's variant by its position in the enum's declaration, derived along with Split
+pub trait VariantIndex {
+    /// number of variants declared by the enum
+    const VARIANT_COUNT: usize;
+    fn variant_index(&self) -> usize;
+}
+
+/// the output of a derived WithVariantUpdate::split, records are bucketed by the VariantIndex
+/// of their variant, i.e. `variants[i]` holds the records whose variant is the enum's i-th variant
+#[derive(Derivative)]
+#[derivative(Clone(bound = "T: Clone"), Debug(bound = "T: Debug"), Default(bound = ""))]
+pub struct VariantSplit<T> {
+    pub variants: Vec<VariantUpdate<T>>,
+}
+
+impl<T> VariantSplit<T> {
+    pub fn new(variant_count: usize) -> Self {
+        Self {
+            variants: (0..variant_count).map(|_| VariantUpdate::default()).collect(),
+        }
+    }
+
+    pub fn variant(&self, variant_index: usize) -> Option<&VariantUpdate<T>> {
+        self.variants.get(variant_index)
+    }
+
+    /// the bucket of the variant, the variant index must be below the variant count
+    pub fn variant_mut(&mut self, variant_index: usize) -> &mut VariantUpdate<T> {
+        debug_assert!(
+            variant_index < self.variants.len(),
+            "variant index {variant_index} out of bounds of {} variants",
+            self.variants.len(),
+        );
+        &mut self.variants[variant_index]
+    }
+}
@@ -1,9 +1,47 @@
 use service_util::*;
+use std::collections::HashMap;
 
-#[derive(Split)]
+#[derive(PartialEq, Split)]
 pub enum Test {
     A(String),
     B(u8),
+    C,
+    D,
 }
 
-fn main() {}
+#[derive(Id, PartialEq, WithVariantUpdate)]
+pub struct TestRecord {
+    id: u32,
+    #[variant]
+    test: Test,
+}
+
+fn main() {
+    let existing = HashMap::from([
+        (
+            1,
+            TestRecord {
+                id: 1,
+                test: Test::A("a".into()),
+            },
+        ),
+        (2, TestRecord { id: 2, test: Test::C }),
+    ]);
+    let records = [
+        TestRecord {
+            id: 1,
+            test: Test::A("b".into()),
+        },
+        TestRecord { id: 2, test: Test::D },
+        TestRecord {
+            id: 3,
+            test: Test::B(3),
+        },
+    ];
+
+    let split = TestRecord::split(records, &existing);
+    assert_eq!(split.variants.len(), 4);
+    assert_eq!(split.variants[0].update.len(), 1);
+    assert_eq!(split.variants[1].insert.len(), 1);
+    assert_eq!(split.variants[3].variant_changed.len(), 1);
+}
@@ -0,0 +1,564 @@
+use itertools::Itertools;
+use proc_macro2::TokenStream;
+use quote::{format_ident, quote};
+use syn::parse::Error;
+use syn::parse2;
+
+const METHODS: &[&str] = &[
+    "CONNECT", "DELETE", "GET", "HEAD", "OPTIONS", "PATCH", "POST", "PUT", "TRACE",
+];
+
+pub fn derive_endpoint(tokens: TokenStream) -> Result<TokenStream, Error> {
+    let ast: syn::DeriveInput = parse2(tokens)?;
+
+    let fields = match &ast.data {
+        syn::Data::Struct(syn::DataStruct {
+            fields: syn::Fields::Named(fields_named),
+            ..
+        }) => fields_named.named.iter().collect_vec(),
+        syn::Data::Struct(syn::DataStruct {
+            fields: syn::Fields::Unit,
+            ..
+        }) => vec![],
+        _ => {
+            return Err(Error::new_spanned(
+                ast,
+                "Endpoint can only be derived for structs with named fields or unit structs",
+            ))
+        }
+    };
+
+    let mut endpoint_attr = None;
+    for attr in &ast.attrs {
+        if attr.path().is_ident("endpoint") {
+            endpoint_attr = Some(parse_endpoint_attr(attr)?);
+        }
+    }
+    let EndpointAttr { method, path, response } = endpoint_attr.ok_or_else(|| {
+        Error::new_spanned(
+            &ast,
+            "Endpoint requires an #[endpoint(method = \"...\", path = \"...\")] attribute",
+        )
+    })?;
+
+    let mut query_fields = vec![];
+    let mut header_fields = vec![];
+    let mut body_field = None;
+    let mut path_fields = vec![];
+    for field in fields {
+        match parse_field_attr(field)? {
+            Some(FieldAttr::Query(rename)) => query_fields.push((field, rename)),
+            Some(FieldAttr::Header(name)) => header_fields.push((field, name)),
+            Some(FieldAttr::Body) => {
+                if body_field.is_some() {
+                    return Err(Error::new_spanned(
+                        field,
+                        "only one field can be marked #[endpoint(body)]",
+                    ));
+                }
+                body_field = Some(field);
+            }
+            None => path_fields.push(field),
+        }
+    }
+
+    let ident = &ast.ident;
+    let vis = &ast.vis;
+    let (impl_generics, type_generics, where_clause) = ast.generics.split_for_impl();
+
+    let method = format_ident!("{method}");
+    let path_fn = path_fn(&path, &path_fields)?;
+    let route = path.value();
+    let route = syn::LitStr::new(route.split('?').next().unwrap_or_default(), path.span());
+
+    let (params_ty, params_fn, params_struct) = if query_fields.is_empty() {
+        (
+            quote!(type Params<'__endpoint> = () where Self: '__endpoint;),
+            quote!(
+                fn params(&self) -> Self::Params<'_> {}
+            ),
+            quote!(),
+        )
+    } else {
+        let params_ident = format_ident!("__{ident}Params");
+        let mut params_generics = ast.generics.clone();
+        params_generics
+            .params
+            .insert(0, syn::GenericParam::Lifetime(parse2(quote!('__endpoint))?));
+        let (_, params_type_generics, params_where_clause) = params_generics.split_for_impl();
+
+        let params_fields = query_fields.iter().map(|(field, rename)| {
+            let field_ident = &field.ident;
+            let field_ty = &field.ty;
+            let rename = rename.as_ref().map(|rename| quote!(#[serde(rename = #rename)]));
+            quote!(#rename #field_ident: &'__endpoint #field_ty)
+        });
+        let field_idents = query_fields.iter().map(|(field, _)| &field.ident).collect_vec();
+
+        (
+            quote!(type Params<'__endpoint> = #params_ident #params_type_generics where Self: '__endpoint;),
+            quote!(
+                fn params(&self) -> Self::Params<'_> {
+                    #params_ident {
+                        #(#field_idents: &self.#field_idents,)*
+                        __endpoint: ::std::marker::PhantomData,
+                    }
+                }
+            ),
+            quote!(
+                #[doc(hidden)]
+                #[allow(non_camel_case_types)]
+                #[derive(Debug, ::service_util::service_util_serde::Serialize)]
+                #[serde(crate = "::service_util::service_util_serde")]
+                #vis struct #params_ident #params_generics #params_where_clause {
+                    #(#params_fields,)*
+                    #[serde(skip)]
+                    __endpoint: ::std::marker::PhantomData<&'__endpoint #ident #type_generics>,
+                }
+            ),
+        )
+    };
+
+    let headers_fn = if header_fields.is_empty() {
+        quote!()
+    } else {
+        let inserts = header_fields.iter().map(|(field, name)| {
+            let field_ident = &field.ident;
+            let insert = quote!(
+                let value = ::service_util::service_util_hyper::header::HeaderValue::from_str(
+                    &::std::string::ToString::to_string(value),
+                )
+                .map_err(|err| ::service_util::BaseClientError::InvalidHeader(::std::format!("{}: {}", #name, err)))?;
+                headers.insert(::service_util::service_util_hyper::header::HeaderName::from_static(#name), value);
+            );
+            if option_inner_ty(&field.ty).is_some() {
+                quote!(if let Some(value) = &self.#field_ident { #insert })
+            } else {
+                quote!({ let value = &self.#field_ident; #insert })
+            }
+        });
+        quote!(
+            fn try_headers(&self) -> Result<::service_util::service_util_hyper::header::HeaderMap, ::service_util::BaseClientError> {
+                let mut headers = ::service_util::service_util_hyper::header::HeaderMap::new();
+                #(#inserts)*
+                Ok(headers)
+            }
+        )
+    };
+
+    let (body_ty, body_fn) = match body_field {
+        None => (quote!(type Body<'__endpoint> = () where Self: '__endpoint;), quote!()),
+        Some(field) => {
+            let field_ident = &field.ident;
+            match option_inner_ty(&field.ty) {
+                Some(inner_ty) => (
+                    quote!(type Body<'__endpoint> = &'__endpoint #inner_ty where Self: '__endpoint;),
+                    quote!(
+                        fn body(&self) -> Option<Self::Body<'_>> {
+                            self.#field_ident.as_ref()
+                        }
+                    ),
+                ),
+                None => {
+                    let field_ty = &field.ty;
+                    (
+                        quote!(type Body<'__endpoint> = &'__endpoint #field_ty where Self: '__endpoint;),
+                        quote!(
+                            fn body(&self) -> Option<Self::Body<'_>> {
+                                Some(&self.#field_ident)
+                            }
+                        ),
+                    )
+                }
+            }
+        }
+    };
+
+    let send = response.map(|response| {
+        quote!(
+            impl #impl_generics #ident #type_generics #where_clause {
+                pub async fn send<C>(&self, client: &C) -> Result<#response, C::Error>
+                where
+                    C: ::service_util::Client,
+                    Self: ::service_util::Query<C, #response>,
+                {
+                    ::service_util::Query::<C, #response>::query(self, client).await
+                }
+            }
+        )
+    });
+
+    let tokens = quote!(
+        #params_struct
+
+        impl #impl_generics ::service_util::Endpoint for #ident #type_generics #where_clause {
+            const METHOD: ::service_util::service_util_hyper::Method = ::service_util::service_util_hyper::Method::#method;
+            const ROUTE: Option<&'static str> = Some(#route);
+
+            #params_ty
+            #body_ty
+            type Response<T> = ::service_util::DefaultResponse<T>;
+            type ErrorBody = ::service_util::ProblemDetails;
+
+            #path_fn
+            #params_fn
+            #headers_fn
+            #body_fn
+        }
+
+        #send
+    );
+
+    Ok(tokens)
+}
+
+struct EndpointAttr {
+    method: String,
+    path: syn::LitStr,
+    response: Option<syn::Type>,
+}
+
+enum FieldAttr {
+    Query(Option<syn::LitStr>),
+    Header(syn::LitStr),
+    Body,
+}
+
+fn parse_endpoint_attr(attr: &syn::Attribute) -> Result<EndpointAttr, Error> {
+    let (mut method, mut path, mut response) = (None, None, None);
+    attr.parse_nested_meta(|meta| {
+        if meta.path.is_ident("method") {
+            let lit: syn::LitStr = meta.value()?.parse()?;
+            let value = lit.value().to_uppercase();
+            if !METHODS.contains(&value.as_str()) {
+                return Err(Error::new_spanned(
+                    lit,
+                    format!("unrecognized method, expected one of {}", METHODS.join(", ")),
+                ));
+            }
+            method = Some(value);
+        } else if meta.path.is_ident("path") {
+            path = Some(meta.value()?.parse()?);
+        } else if meta.path.is_ident("response") {
+            response = Some(meta.value()?.parse()?);
+        } else {
+            return Err(meta.error("unrecognized endpoint argument"));
+        }
+        Ok(())
+    })?;
+    Ok(EndpointAttr {
+        method: method.ok_or_else(|| Error::new_spanned(attr, "missing required argument `method`"))?,
+        path: path.ok_or_else(|| Error::new_spanned(attr, "missing required argument `path`"))?,
+        response,
+    })
+}
+
+fn parse_field_attr(field: &syn::Field) -> Result<Option<FieldAttr>, Error> {
+    let mut field_attr = None;
+    for attr in &field.attrs {
+        if !attr.path().is_ident("endpoint") {
+            continue;
+        }
+        attr.parse_nested_meta(|meta| {
+            if field_attr.is_some() {
+                return Err(meta.error("a field can only be one of `query`, `header` or `body`"));
+            }
+            if meta.path.is_ident("query") {
+                field_attr = Some(FieldAttr::Query(
+                    match meta.input.is_empty() || meta.input.peek(syn::Token![,]) {
+                        true => None,
+                        false => Some(meta.value()?.parse()?),
+                    },
+                ));
+            } else if meta.path.is_ident("header") {
+                let lit: syn::LitStr = meta.value()?.parse()?;
+                let name = lit.value().to_lowercase();
+                if name.is_empty() || !name.bytes().all(is_header_name_byte) {
+                    return Err(Error::new_spanned(lit, "invalid header name"));
+                }
+                field_attr = Some(FieldAttr::Header(syn::LitStr::new(&name, lit.span())));
+            } else if meta.path.is_ident("body") {
+                field_attr = Some(FieldAttr::Body);
+            } else {
+                return Err(
+                    meta.error("unrecognized endpoint field argument, expected one of `query`, `header` or `body`")
+                );
+            }
+            Ok(())
+        })?;
+    }
+    Ok(field_attr)
+}
+
+/// splits the path template into a format string and the fields interpolated into it,
+/// every field which isn't a query param, header or the body must be used in the path;
+/// braces are reserved for path parameters, literal braces must be percent-encoded
+fn path_fn(path: &syn::LitStr, path_fields: &[&syn::Field]) -> Result<TokenStream, Error> {
+    let template = path.value();
+    let mut format = String::with_capacity(template.len());
+    let mut args = vec![];
+    let mut rest = template.as_str();
+    let unmatched_brace = || Error::new_spanned(path, "unmatched `}` in path, percent-encode literal braces as %7D");
+    while let Some(start) = rest.find('{') {
+        if rest[..start].contains('}') {
+            return Err(unmatched_brace());
+        }
+        let end = rest[start..]
+            .find('}')
+            .map(|end| start + end)
+            .ok_or_else(|| Error::new_spanned(path, "unclosed `{` in path"))?;
+        let name = &rest[start + 1..end];
+        if name.contains('{') {
+            return Err(Error::new_spanned(
+                path,
+                "nested `{` in path, percent-encode literal braces as %7B",
+            ));
+        }
+        let field = path_fields
+            .iter()
+            .find(|field| field.ident.as_ref().is_some_and(|ident| ident == name))
+            .ok_or_else(|| Error::new_spanned(path, format!("path parameter `{name}` does not match a field")))?;
+        format.push_str(&rest[..start]);
+        format.push_str("{}");
+        args.push(&field.ident);
+        rest = &rest[end + 1..];
+    }
+    if rest.contains('}') {
+        return Err(unmatched_brace());
+    }
+    format.push_str(rest);
+
+    if let Some(unused) = path_fields.iter().find(|field| !args.contains(&&field.ident)) {
+        return Err(Error::new_spanned(
+            unused,
+            "field is not used in the path, mark it with one of #[endpoint(query)], #[endpoint(header = \"...\")] or #[endpoint(body)]",
+        ));
+    }
+
+    Ok(if args.is_empty() {
+        quote!(
+            fn path(&self) -> ::service_util::Path {
+                ::service_util::Path::from(#path)
+            }
+        )
+    } else {
+        quote!(
+            fn path(&self) -> ::service_util::Path {
+                let path = ::std::format!(#format, #(::service_util::encode_path_segment(&self.#args)),*);
+                ::service_util::Path::from(path).with_template(#path)
+            }
+        )
+    })
+}
+
+fn option_inner_ty(ty: &syn::Type) -> Option<&syn::Type> {
+    let syn::Type::Path(type_path) = ty else {
+        return None;
+    };
+    let segment = type_path.path.segments.last()?;
+    if segment.ident != "Option" {
+        return None;
+    }
+    let syn::PathArguments::AngleBracketed(args) = &segment.arguments else {
+        return None;
+    };
+    match args.args.first()? {
+        syn::GenericArgument::Type(ty) if args.args.len() == 1 => Some(ty),
+        _ => None,
+    }
+}
+
+fn is_header_name_byte(byte: u8) -> bool {
+    byte.is_ascii_lowercase() || byte.is_ascii_digit() || b"!#$%&'*+-.^_`|~".contains(&byte)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_endpoint_unit() {
+        let tokens = quote!(
+            #[derive(Endpoint)]
+            #[endpoint(method = "get", path = "/health")]
+            pub struct TestEndpoint;
+        );
+
+        let output = derive_endpoint(tokens).unwrap();
+
+        let ty = format_ident!("TestEndpoint");
+        let expected = quote!(
+            impl ::service_util::Endpoint for #ty {
+                const METHOD: ::service_util::service_util_hyper::Method = ::service_util::service_util_hyper::Method::GET;
+                const ROUTE: Option<&'static str> = Some("/health");
+
+                type Params<'__endpoint> = () where Self: '__endpoint;
+                type Body<'__endpoint> = () where Self: '__endpoint;
+                type Response<T> = ::service_util::DefaultResponse<T>;
+                type ErrorBody = ::service_util::ProblemDetails;
+
+                fn path(&self) -> ::service_util::Path {
+                    ::service_util::Path::from("/health")
+                }
+                fn params(&self) -> Self::Params<'_> {}
+            }
+        );
+
+        assert_eq!(output.to_string(), expected.to_string());
+    }
+
+    #[test]
+    fn test_endpoint_fields() {
+        let tokens = quote!(
+            #[derive(Endpoint)]
+            #[endpoint(method = "PATCH", path = "/orgs/{org}/users/{id}?view=full", response = User)]
+            pub struct TestEndpoint<'a> {
+                org: &'a str,
+                id: u32,
+                #[endpoint(query)]
+                dry_run: bool,
+                #[endpoint(query = "pageSize")]
+                page_size: Option<u32>,
+                #[endpoint(header = "X-Request-Id")]
+                request_id: Option<String>,
+                #[endpoint(header = "x-tenant")]
+                tenant: &'a str,
+                #[endpoint(body)]
+                body: Option<UpdateUser>,
+            }
+        );
+
+        let output = derive_endpoint(tokens).unwrap();
+
+        let ty = format_ident!("TestEndpoint");
+        let params_ty = format_ident!("__TestEndpointParams");
+        let expected = quote!(
+            #[doc(hidden)]
+            #[allow(non_camel_case_types)]
+            #[derive(Debug, ::service_util::service_util_serde::Serialize)]
+            #[serde(crate = "::service_util::service_util_serde")]
+            pub struct #params_ty<'__endpoint, 'a> {
+                dry_run: &'__endpoint bool,
+                #[serde(rename = "pageSize")]
+                page_size: &'__endpoint Option<u32>,
+                #[serde(skip)]
+                __endpoint: ::std::marker::PhantomData<&'__endpoint #ty<'a> >,
+            }
+
+            impl<'a> ::service_util::Endpoint for #ty<'a> {
+                const METHOD: ::service_util::service_util_hyper::Method = ::service_util::service_util_hyper::Method::PATCH;
+                const ROUTE: Option<&'static str> = Some("/orgs/{org}/users/{id}");
+
+                type Params<'__endpoint> = #params_ty<'__endpoint, 'a> where Self: '__endpoint;
+                type Body<'__endpoint> = &'__endpoint UpdateUser where Self: '__endpoint;
+                type Response<T> = ::service_util::DefaultResponse<T>;
+                type ErrorBody = ::service_util::ProblemDetails;
+
+                fn path(&self) -> ::service_util::Path {
+                    let path = ::std::format!(
+                        "/orgs/{}/users/{}?view=full",
+                        ::service_util::encode_path_segment(&self.org),
+                        ::service_util::encode_path_segment(&self.id)
+                    );
+                    ::service_util::Path::from(path).with_template("/orgs/{org}/users/{id}?view=full")
+                }
+                fn params(&self) -> Self::Params<'_> {
+                    #params_ty {
+                        dry_run: &self.dry_run,
+                        page_size: &self.page_size,
+                        __endpoint: ::std::marker::PhantomData,
+                    }
+                }
+                fn try_headers(&self) -> Result<::service_util::service_util_hyper::header::HeaderMap, ::service_util::BaseClientError> {
+                    let mut headers = ::service_util::service_util_hyper::header::HeaderMap::new();
+                    if let Some(value) = &self.request_id {
+                        let value = ::service_util::service_util_hyper::header::HeaderValue::from_str(
+                            &::std::string::ToString::to_string(value),
+                        )
+                        .map_err(|err| ::service_util::BaseClientError::InvalidHeader(::std::format!("{}: {}", "x-request-id", err)))?;
+                        headers.insert(::service_util::service_util_hyper::header::HeaderName::from_static("x-request-id"), value);
+                    }
+                    {
+                        let value = &self.tenant;
+                        let value = ::service_util::service_util_hyper::header::HeaderValue::from_str(
+                            &::std::string::ToString::to_string(value),
+                        )
+                        .map_err(|err| ::service_util::BaseClientError::InvalidHeader(::std::format!("{}: {}", "x-tenant", err)))?;
+                        headers.insert(::service_util::service_util_hyper::header::HeaderName::from_static("x-tenant"), value);
+                    }
+                    Ok(headers)
+                }
+                fn body(&self) -> Option<Self::Body<'_>> {
+                    self.body.as_ref()
+                }
+            }
+
+            impl<'a> #ty<'a> {
+                pub async fn send<C>(&self, client: &C) -> Result<User, C::Error>
+                where
+                    C: ::service_util::Client,
+                    Self: ::service_util::Query<C, User>,
+                {
+                    ::service_util::Query::<C, User>::query(self, client).await
+                }
+            }
+        );
+
+        assert_eq!(output.to_string(), expected.to_string());
+    }
+
+    #[test]
+    fn test_endpoint_invalid() {
+        let missing_attribute = quote!(
+            #[derive(Endpoint)]
+            pub struct TestEndpoint;
+        );
+        assert!(derive_endpoint(missing_attribute).is_err());
+
+        let unrecognized_method = quote!(
+            #[derive(Endpoint)]
+            #[endpoint(method = "FETCH", path = "/")]
+            pub struct TestEndpoint;
+        );
+        assert!(derive_endpoint(unrecognized_method).is_err());
+
+        let unmatched_path_param = quote!(
+            #[derive(Endpoint)]
+            #[endpoint(method = "GET", path = "/users/{id}")]
+            pub struct TestEndpoint {
+                user_id: u32,
+            }
+        );
+        assert!(derive_endpoint(unmatched_path_param).is_err());
+
+        let unused_field = quote!(
+            #[derive(Endpoint)]
+            #[endpoint(method = "GET", path = "/users")]
+            pub struct TestEndpoint {
+                id: u32,
+            }
+        );
+        assert!(derive_endpoint(unused_field).is_err());
+
+        let invalid_header = quote!(
+            #[derive(Endpoint)]
+            #[endpoint(method = "GET", path = "/users")]
+            pub struct TestEndpoint {
+                #[endpoint(header = "x foo")]
+                foo: String,
+            }
+        );
+        assert!(derive_endpoint(invalid_header).is_err());
+
+        for path in ["/users/{id}}", "/users/}{id}", "/users/{{id}}", "/users/{id"] {
+            let literal_brace = quote!(
+                #[derive(Endpoint)]
+                #[endpoint(method = "GET", path = #path)]
+                pub struct TestEndpoint {
+                    id: u32,
+                }
+            );
+            assert!(derive_endpoint(literal_brace).is_err(), "{path}");
+        }
+    }
+}
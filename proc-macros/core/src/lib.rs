@@ -1,7 +1,11 @@
+mod endpoint;
 mod id;
+mod pageable;
 mod split;
 mod with_variant_update;
 
+pub use endpoint::*;
 pub use id::*;
+pub use pageable::*;
 pub use split::*;
 pub use with_variant_update::*;
@@ -0,0 +1,296 @@
+use proc_macro2::TokenStream;
+use quote::quote;
+use syn::parse::Error;
+use syn::parse2;
+
+pub fn derive_pageable(tokens: TokenStream) -> Result<TokenStream, Error> {
+    let ast: syn::DeriveInput = parse2(tokens)?;
+
+    let mut strategy = None;
+    for attr in &ast.attrs {
+        if attr.path().is_ident("pageable") {
+            strategy = Some(parse_strategy(attr)?);
+        }
+    }
+    let strategy = strategy.ok_or_else(|| {
+        Error::new_spanned(
+            &ast,
+            "Pageable requires a #[pageable(...)] attribute specifying a pagination strategy",
+        )
+    })?;
+
+    let next_page = match strategy {
+        Strategy::Link => quote!(::service_util::NextPage::link_header(prev_response)),
+        Strategy::Cursor { field, param } => {
+            quote!(::service_util::NextPage::cursor(prev_response, #field, #param))
+        }
+        Strategy::Offset { param, page_size } => {
+            let page_size = match page_size {
+                Some(page_size) => quote!(Some(#page_size)),
+                None => quote!(None),
+            };
+            quote!(::service_util::NextPage::offset(prev_page, #param, #page_size))
+        }
+        Strategy::PageNumber { param, first } => {
+            let first = match first {
+                Some(first) => quote!(#first),
+                None => quote!(1),
+            };
+            quote!(::service_util::NextPage::page_number(prev_page, #param, #first))
+        }
+    };
+
+    let ident = &ast.ident;
+    let (impl_generics, type_generics, where_clause) = ast.generics.split_for_impl();
+
+    let tokens = quote!(
+        impl #impl_generics ::service_util::Pageable for #ident #type_generics #where_clause {
+            #[allow(unused_variables)]
+            fn next_page(
+                prev_response: &::service_util::service_util_hyper::Response<Vec<u8>>,
+                prev_page: &::service_util::PrevPage,
+            ) -> Option<::service_util::NextPage> {
+                #next_page
+            }
+        }
+    );
+
+    Ok(tokens)
+}
+
+enum Strategy {
+    Link,
+    Cursor {
+        field: syn::LitStr,
+        param: syn::LitStr,
+    },
+    Offset {
+        param: syn::LitStr,
+        page_size: Option<syn::LitInt>,
+    },
+    PageNumber {
+        param: syn::LitStr,
+        first: Option<syn::LitInt>,
+    },
+}
+
+fn parse_strategy(attr: &syn::Attribute) -> Result<Strategy, Error> {
+    let mut strategy = None;
+    attr.parse_nested_meta(|meta| {
+        if meta.path.is_ident("link") {
+            strategy = Some(Strategy::Link);
+            return Ok(());
+        }
+
+        let (name, arguments): (_, &[_]) = if meta.path.is_ident("cursor") {
+            ("cursor", &["field", "param"])
+        } else if meta.path.is_ident("offset") {
+            ("offset", &["param", "page_size"])
+        } else if meta.path.is_ident("page_number") {
+            ("page_number", &["param", "first"])
+        } else {
+            return Err(meta.error(
+                "unrecognized pagination strategy, expected one of `link`, `cursor`, `offset` or `page_number`",
+            ));
+        };
+
+        let (mut field, mut param, mut page_size, mut first) = (None, None, None, None);
+        meta.parse_nested_meta(|meta| {
+            let argument = ["field", "param", "page_size", "first"]
+                .into_iter()
+                .find(|argument| meta.path.is_ident(argument))
+                .ok_or_else(|| meta.error("unrecognized pageable argument"))?;
+            if !arguments.contains(&argument) {
+                return Err(meta.error(format!(
+                    "argument `{argument}` does not apply to the `{name}` pagination strategy"
+                )));
+            }
+            match argument {
+                "field" => field = Some(meta.value()?.parse()?),
+                "param" => param = Some(meta.value()?.parse()?),
+                "page_size" => page_size = Some(meta.value()?.parse()?),
+                _ => first = Some(meta.value()?.parse()?),
+            }
+            Ok(())
+        })?;
+        let param = param.ok_or_else(|| meta.error("missing required argument `param`"))?;
+
+        strategy = Some(match name {
+            "cursor" => Strategy::Cursor {
+                field: field.ok_or_else(|| meta.error("missing required argument `field`"))?,
+                param,
+            },
+            "offset" => Strategy::Offset { param, page_size },
+            _ => Strategy::PageNumber { param, first },
+        });
+        Ok(())
+    })?;
+    strategy.ok_or_else(|| Error::new_spanned(attr, "missing pagination strategy"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use quote::format_ident;
+
+    #[test]
+    fn test_pageable_link() {
+        let tokens = quote!(
+            #[derive(Pageable)]
+            #[pageable(link)]
+            pub struct TestEndpoint;
+        );
+
+        let output = derive_pageable(tokens).unwrap();
+
+        let ty = format_ident!("TestEndpoint");
+        let expected = quote!(
+            impl ::service_util::Pageable for #ty {
+                #[allow(unused_variables)]
+                fn next_page(
+                    prev_response: &::service_util::service_util_hyper::Response<Vec<u8>>,
+                    prev_page: &::service_util::PrevPage,
+                ) -> Option<::service_util::NextPage> {
+                    ::service_util::NextPage::link_header(prev_response)
+                }
+            }
+        );
+
+        assert_eq!(output.to_string(), expected.to_string());
+    }
+
+    #[test]
+    fn test_pageable_cursor() {
+        let tokens = quote!(
+            #[derive(Pageable)]
+            #[pageable(cursor(field = "/meta/next_cursor", param = "cursor"))]
+            pub struct TestEndpoint {
+                id: u32,
+            }
+        );
+
+        let output = derive_pageable(tokens).unwrap();
+
+        let ty = format_ident!("TestEndpoint");
+        let expected = quote!(
+            impl ::service_util::Pageable for #ty {
+                #[allow(unused_variables)]
+                fn next_page(
+                    prev_response: &::service_util::service_util_hyper::Response<Vec<u8>>,
+                    prev_page: &::service_util::PrevPage,
+                ) -> Option<::service_util::NextPage> {
+                    ::service_util::NextPage::cursor(prev_response, "/meta/next_cursor", "cursor")
+                }
+            }
+        );
+
+        assert_eq!(output.to_string(), expected.to_string());
+    }
+
+    #[test]
+    fn test_pageable_offset() {
+        let tokens = quote!(
+            #[derive(Pageable)]
+            #[pageable(offset(param = "offset", page_size = 100))]
+            pub struct TestEndpoint<'a> {
+                name: &'a str,
+            }
+        );
+
+        let output = derive_pageable(tokens).unwrap();
+
+        let ty = format_ident!("TestEndpoint");
+        let expected = quote!(
+            impl<'a> ::service_util::Pageable for #ty<'a> {
+                #[allow(unused_variables)]
+                fn next_page(
+                    prev_response: &::service_util::service_util_hyper::Response<Vec<u8>>,
+                    prev_page: &::service_util::PrevPage,
+                ) -> Option<::service_util::NextPage> {
+                    ::service_util::NextPage::offset(prev_page, "offset", Some(100))
+                }
+            }
+        );
+
+        assert_eq!(output.to_string(), expected.to_string());
+    }
+
+    #[test]
+    fn test_pageable_page_number() {
+        let tokens = quote!(
+            #[derive(Pageable)]
+            #[pageable(page_number(param = "page"))]
+            pub struct TestEndpoint;
+        );
+
+        let output = derive_pageable(tokens).unwrap();
+
+        let ty = format_ident!("TestEndpoint");
+        let expected = quote!(
+            impl ::service_util::Pageable for #ty {
+                #[allow(unused_variables)]
+                fn next_page(
+                    prev_response: &::service_util::service_util_hyper::Response<Vec<u8>>,
+                    prev_page: &::service_util::PrevPage,
+                ) -> Option<::service_util::NextPage> {
+                    ::service_util::NextPage::page_number(prev_page, "page", 1)
+                }
+            }
+        );
+
+        assert_eq!(output.to_string(), expected.to_string());
+    }
+
+    #[test]
+    fn test_pageable_invalid() {
+        let missing_attribute = quote!(
+            #[derive(Pageable)]
+            pub struct TestEndpoint;
+        );
+        assert!(derive_pageable(missing_attribute).is_err());
+
+        let unrecognized_strategy = quote!(
+            #[derive(Pageable)]
+            #[pageable(token(param = "token"))]
+            pub struct TestEndpoint;
+        );
+        assert!(derive_pageable(unrecognized_strategy).is_err());
+
+        let missing_field = quote!(
+            #[derive(Pageable)]
+            #[pageable(cursor(param = "cursor"))]
+            pub struct TestEndpoint;
+        );
+        assert!(derive_pageable(missing_field).is_err());
+
+        let unrecognized_argument = quote!(
+            #[derive(Pageable)]
+            #[pageable(offset(param = "offset", limit = 10))]
+            pub struct TestEndpoint;
+        );
+        assert!(derive_pageable(unrecognized_argument).is_err());
+    }
+
+    #[test]
+    fn test_pageable_inapplicable_arguments() {
+        for attr in [
+            quote!(#[pageable(cursor(field = "next", param = "cursor", page_size = 10))]),
+            quote!(#[pageable(cursor(field = "next", param = "cursor", first = 0))]),
+            quote!(#[pageable(offset(field = "next", param = "offset"))]),
+            quote!(#[pageable(offset(param = "offset", first = 0))]),
+            quote!(#[pageable(page_number(param = "page", page_size = 10))]),
+            quote!(#[pageable(page_number(field = "next", param = "page"))]),
+        ] {
+            let tokens = quote!(
+                #[derive(Pageable)]
+                #attr
+                pub struct TestEndpoint;
+            );
+            let err = derive_pageable(tokens).unwrap_err();
+            assert!(
+                err.to_string().contains("does not apply to the"),
+                "{attr}: {err}"
+            );
+        }
+    }
+}
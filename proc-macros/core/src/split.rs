@@ -74,6 +74,17 @@ pub fn derive_split(tokens: TokenStream) -> Result<TokenStream, Error> {
         })
         .collect_vec();
 
+    let variant_count = data_enum.variants.len();
+    let variant_indices = data_enum
+        .variants
+        .iter()
+        .enumerate()
+        .map(|(variant_index, variant)| {
+            let variant_ident = &variant.ident;
+            quote!(#ident::#variant_ident { .. } => #variant_index)
+        })
+        .collect_vec();
+
     let lt: syn::Lifetime = parse2(quote!('split))?;
 
     let components_ty = quote!((#(Option<#component_tys>,)*));
@@ -95,6 +106,13 @@ pub fn derive_split(tokens: TokenStream) -> Result<TokenStream, Error> {
             }
         }
 
+        impl #impl_generics ::service_util::VariantIndex for #ident #type_generics #where_clause {
+            const VARIANT_COUNT: usize = #variant_count;
+            fn variant_index(&self) -> usize {
+                match self { #(#variant_indices,)* }
+            }
+        }
+
         impl #ref_impl_generics ::service_util::Split for &#lt #ident #type_generics #where_clause {
             type Components = #ref_components_ty;
             fn split(self) -> Self::Components {
@@ -160,6 +178,17 @@ mod tests {
                 }
             }
 
+            impl ::service_util::VariantIndex for #ty {
+                const VARIANT_COUNT: usize = 3usize;
+                fn variant_index(&self) -> usize {
+                    match self {
+                        #ty::A { .. } => 0usize,
+                        #ty::B { .. } => 1usize,
+                        #ty::C { .. } => 2usize,
+                    }
+                }
+            }
+
             impl<'split> ::service_util::Split for &'split #ty {
                 type Components = (Option<&'split i32>, Option<&'split u32>, Option<&'split u64>, Option<&'split String>,);
                 fn split(self) -> Self::Components {
@@ -230,6 +259,17 @@ mod tests {
                 }
             }
 
+            impl ::service_util::VariantIndex for #ty {
+                const VARIANT_COUNT: usize = 3usize;
+                fn variant_index(&self) -> usize {
+                    match self {
+                        #ty::A { .. } => 0usize,
+                        #ty::B { .. } => 1usize,
+                        #ty::C { .. } => 2usize,
+                    }
+                }
+            }
+
             impl<'split> ::service_util::Split for &'split #ty {
                 type Components = (Option<&'split i32>, Option<&'split u32>, Option<&'split u64>, Option<&'split String>,);
                 fn split(self) -> Self::Components {
@@ -304,6 +344,19 @@ mod tests {
                 }
             }
 
+            impl ::service_util::VariantIndex for #ty {
+                const VARIANT_COUNT: usize = 5usize;
+                fn variant_index(&self) -> usize {
+                    match self {
+                        #ty::A { .. } => 0usize,
+                        #ty::B { .. } => 1usize,
+                        #ty::C { .. } => 2usize,
+                        #ty::D { .. } => 3usize,
+                        #ty::E { .. } => 4usize,
+                    }
+                }
+            }
+
             impl<'split> ::service_util::Split for &'split #ty {
                 type Components = (Option<&'split u8>, Option<&'split u16>, Option<&'split u32>, Option<&'split u64>, Option<&'split i8>, Option<&'split i16>,);
                 fn split(self) -> Self::Components {
@@ -21,10 +21,11 @@ pub fn derive_with_variant_update(tokens: TokenStream) -> Result<TokenStream, Er
     let field_attributes = data_struct
         .fields
         .iter()
-        .filter_map(parse_field_for_attribute)
+        .enumerate()
+        .filter_map(|(i, field)| parse_field_for_attribute(i, field))
         .collect_vec();
 
-    let variant_field = field_attributes
+    let (variant_field_index, variant_field) = field_attributes
         .iter()
         .find(|x| x.is_variant())
         .ok_or_else(|| {
@@ -34,6 +35,10 @@ pub fn derive_with_variant_update(tokens: TokenStream) -> Result<TokenStream, Er
             )
         })?
         .unwrap_variant();
+    let variant_accessor = variant_field.ident.as_ref().map(|x| quote!(#x)).unwrap_or_else(|| {
+        let index = syn::Index::from(variant_field_index);
+        quote!(#index)
+    });
 
     let ident = &ast.ident;
     let (impl_generics, type_generics, where_clause) = ast.generics.split_for_impl();
@@ -43,9 +48,31 @@ pub fn derive_with_variant_update(tokens: TokenStream) -> Result<TokenStream, Er
     let tokens = quote!(
         impl #impl_generics ::service_util::WithVariantUpdate for #ident #type_generics #where_clause {
             type Variant = #variant_ty;
-            type Split = ();
+            type Split = ::service_util::VariantSplit<Self>;
 
-            fn split(self, existing: &HashMap<Self::Id, Self>) -> Self::Split;
+            fn split(
+                records: impl IntoIterator<Item = Self>,
+                existing: &::std::collections::HashMap<<Self as ::service_util::Id>::Id, Self>,
+            ) -> Self::Split {
+                let mut split = ::service_util::VariantSplit::new(<#variant_ty as ::service_util::VariantIndex>::VARIANT_COUNT);
+                for record in records {
+                    let variant_index = ::service_util::VariantIndex::variant_index(&record.#variant_accessor);
+                    let bucket = split.variant_mut(variant_index);
+                    match existing.get(&::service_util::Id::id(&record)) {
+                        None => bucket.insert.push(record),
+                        Some(existing) => {
+                            if ::service_util::VariantIndex::variant_index(&existing.#variant_accessor) != variant_index {
+                                bucket.variant_changed.push(record);
+                            } else if *existing == record {
+                                bucket.unchanged.push(record);
+                            } else {
+                                bucket.update.push(record);
+                            }
+                        }
+                    }
+                }
+                split
+            }
         }
     );
 
@@ -54,14 +81,127 @@ pub fn derive_with_variant_update(tokens: TokenStream) -> Result<TokenStream, Er
 
 #[derive(Clone, Copy, IsVariant, Unwrap)]
 enum FieldAttribute<'a> {
-    Variant(&'a syn::Field),
+    Variant(usize, &'a syn::Field),
 }
 
-fn parse_field_for_attribute(field: &syn::Field) -> Option<FieldAttribute<'_>> {
+fn parse_field_for_attribute(index: usize, field: &syn::Field) -> Option<FieldAttribute<'_>> {
     for attr in &field.attrs {
         if attr.path().is_ident("variant") {
-            return Some(FieldAttribute::Variant(field));
+            return Some(FieldAttribute::Variant(index, field));
         }
     }
     None
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use quote::format_ident;
+
+    #[test]
+    fn test_with_variant_update_named() {
+        let tokens = quote!(
+            #[derive(WithVariantUpdate)]
+            pub struct TestStruct {
+                id: u32,
+                #[variant]
+                kind: TestEnum,
+            }
+        );
+
+        let output = derive_with_variant_update(tokens).unwrap();
+
+        let ty = format_ident!("TestStruct");
+        let variant_ty = format_ident!("TestEnum");
+        let expected = quote!(
+            impl ::service_util::WithVariantUpdate for #ty {
+                type Variant = #variant_ty;
+                type Split = ::service_util::VariantSplit<Self>;
+
+                fn split(
+                    records: impl IntoIterator<Item = Self>,
+                    existing: &::std::collections::HashMap<<Self as ::service_util::Id>::Id, Self>,
+                ) -> Self::Split {
+                    let mut split = ::service_util::VariantSplit::new(<#variant_ty as ::service_util::VariantIndex>::VARIANT_COUNT);
+                    for record in records {
+                        let variant_index = ::service_util::VariantIndex::variant_index(&record.kind);
+                        let bucket = split.variant_mut(variant_index);
+                        match existing.get(&::service_util::Id::id(&record)) {
+                            None => bucket.insert.push(record),
+                            Some(existing) => {
+                                if ::service_util::VariantIndex::variant_index(&existing.kind) != variant_index {
+                                    bucket.variant_changed.push(record);
+                                } else if *existing == record {
+                                    bucket.unchanged.push(record);
+                                } else {
+                                    bucket.update.push(record);
+                                }
+                            }
+                        }
+                    }
+                    split
+                }
+            }
+        );
+
+        assert_eq!(output.to_string(), expected.to_string());
+    }
+
+    #[test]
+    fn test_with_variant_update_unnamed() {
+        let tokens = quote!(
+            #[derive(WithVariantUpdate)]
+            pub struct TestStruct(#[id] u32, String, #[variant] TestEnum);
+        );
+
+        let output = derive_with_variant_update(tokens).unwrap();
+
+        let ty = format_ident!("TestStruct");
+        let variant_ty = format_ident!("TestEnum");
+        let expected = quote!(
+            impl ::service_util::WithVariantUpdate for #ty {
+                type Variant = #variant_ty;
+                type Split = ::service_util::VariantSplit<Self>;
+
+                fn split(
+                    records: impl IntoIterator<Item = Self>,
+                    existing: &::std::collections::HashMap<<Self as ::service_util::Id>::Id, Self>,
+                ) -> Self::Split {
+                    let mut split = ::service_util::VariantSplit::new(<#variant_ty as ::service_util::VariantIndex>::VARIANT_COUNT);
+                    for record in records {
+                        let variant_index = ::service_util::VariantIndex::variant_index(&record.2);
+                        let bucket = split.variant_mut(variant_index);
+                        match existing.get(&::service_util::Id::id(&record)) {
+                            None => bucket.insert.push(record),
+                            Some(existing) => {
+                                if ::service_util::VariantIndex::variant_index(&existing.2) != variant_index {
+                                    bucket.variant_changed.push(record);
+                                } else if *existing == record {
+                                    bucket.unchanged.push(record);
+                                } else {
+                                    bucket.update.push(record);
+                                }
+                            }
+                        }
+                    }
+                    split
+                }
+            }
+        );
+
+        assert_eq!(output.to_string(), expected.to_string());
+    }
+
+    #[test]
+    fn test_with_variant_update_missing_variant() {
+        let tokens = quote!(
+            #[derive(WithVariantUpdate)]
+            pub struct TestStruct {
+                id: u32,
+                kind: TestEnum,
+            }
+        );
+
+        assert!(derive_with_variant_update(tokens).is_err());
+    }
+}
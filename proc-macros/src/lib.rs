@@ -2,6 +2,14 @@
 
 use proc_macro::TokenStream;
 
+#[proc_macro_derive(Endpoint, attributes(endpoint))]
+pub fn derive_endpoint(tokens: TokenStream) -> TokenStream {
+    match core::derive_endpoint(tokens.into()) {
+        Ok(tokens) => tokens.into(),
+        Err(err) => err.into_compile_error().into(),
+    }
+}
+
 #[proc_macro_derive(Id, attributes(id))]
 pub fn derive_id(tokens: TokenStream) -> TokenStream {
     match core::derive_id(tokens.into()) {
@@ -10,6 +18,14 @@ pub fn derive_id(tokens: TokenStream) -> TokenStream {
     }
 }
 
+#[proc_macro_derive(Pageable, attributes(pageable))]
+pub fn derive_pageable(tokens: TokenStream) -> TokenStream {
+    match core::derive_pageable(tokens.into()) {
+        Ok(tokens) => tokens.into(),
+        Err(err) => err.into_compile_error().into(),
+    }
+}
+
 #[proc_macro_derive(Split, attributes(split))]
 pub fn derive_split(tokens: TokenStream) -> TokenStream {
     match core::derive_split(tokens.into()) {
@@ -18,7 +34,7 @@ pub fn derive_split(tokens: TokenStream) -> TokenStream {
     }
 }
 
-#[proc_macro_derive(WithVariantUpdate, attributes(with_variant_update))]
+#[proc_macro_derive(WithVariantUpdate, attributes(variant))]
 pub fn derive_with_variant_update(tokens: TokenStream) -> TokenStream {
     match core::derive_with_variant_update(tokens.into()) {
         Ok(tokens) => tokens.into(),